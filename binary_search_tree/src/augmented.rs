@@ -0,0 +1,533 @@
+use std::cmp::Ordering;
+use std::marker::PhantomData;
+use std::ops::Add;
+
+use crate::{InvariantViolation, Node, BST};
+
+/// An associative way of combining the values of a tree into one summary,
+/// with an identity element.
+///
+/// `combine` must be associative and `identity` must leave any summary
+/// unchanged when combined with it on either side. Summaries are always
+/// combined in key order, so `combine` doesn't need to be commutative.
+pub trait Monoid<V> {
+    type Summary: Clone;
+
+    /// Returns the summary of an empty range.
+    fn identity() -> Self::Summary;
+
+    /// Returns the summary of a single value.
+    fn summarize(value: &V) -> Self::Summary;
+
+    /// Returns the summary of two adjacent ranges, `left` coming before
+    /// `right`.
+    fn combine(left: &Self::Summary, right: &Self::Summary) -> Self::Summary;
+}
+
+/// Sums the values, with `V::default()` as zero.
+pub struct Sum;
+
+/// Keeps the largest value, or `None` for an empty range.
+pub struct Max;
+
+/// Keeps the smallest value, or `None` for an empty range.
+pub struct Min;
+
+impl<V: Clone + Default + Add<Output = V>> Monoid<V> for Sum {
+    type Summary = V;
+
+    fn identity() -> V {
+        V::default()
+    }
+
+    fn summarize(value: &V) -> V {
+        value.clone()
+    }
+
+    fn combine(left: &V, right: &V) -> V {
+        left.clone() + right.clone()
+    }
+}
+
+impl<V: Clone + Ord> Monoid<V> for Max {
+    type Summary = Option<V>;
+
+    fn identity() -> Option<V> {
+        None
+    }
+
+    fn summarize(value: &V) -> Option<V> {
+        Some(value.clone())
+    }
+
+    fn combine(left: &Option<V>, right: &Option<V>) -> Option<V> {
+        left.clone().max(right.clone())
+    }
+}
+
+impl<V: Clone + Ord> Monoid<V> for Min {
+    type Summary = Option<V>;
+
+    fn identity() -> Option<V> {
+        None
+    }
+
+    fn summarize(value: &V) -> Option<V> {
+        Some(value.clone())
+    }
+
+    fn combine(left: &Option<V>, right: &Option<V>) -> Option<V> {
+        match (left, right) {
+            (Some(left), Some(right)) => Some(left.clone().min(right.clone())),
+            (Some(_), None) => left.clone(),
+            (None, _) => right.clone(),
+        }
+    }
+}
+
+/// The value of a node together with the summary of its subtree.
+#[derive(Clone)]
+pub(crate) struct Augmented<V, S> {
+    pub(crate) value: V,
+    pub(crate) summary: S,
+}
+
+type AugmentedNode<V, M> = Node<Augmented<V, <M as Monoid<V>>::Summary>>;
+
+/// A Binary Search Tree whose nodes also store the summary of their
+/// subtree under the monoid `M`.
+///
+/// Summaries are kept up to date by every write, the same way `size` is in a
+/// `BST`, so the summary of any key range can be computed in O(height)
+/// time with `aggregate_range`.
+pub struct AugmentedBST<V: Clone, M: Monoid<V>> {
+    pub(crate) tree: BST<Augmented<V, M::Summary>>,
+    monoid: PhantomData<M>,
+}
+
+impl<V: Clone, M: Monoid<V>> Default for AugmentedBST<V, M> {
+    fn default() -> Self {
+        AugmentedBST::new()
+    }
+}
+
+impl<V: Clone, M: Monoid<V>> AugmentedBST<V, M> {
+    /// Creates a new empty tree.
+    pub fn new() -> Self {
+        AugmentedBST {
+            tree: BST::new(),
+            monoid: PhantomData,
+        }
+    }
+
+    /// Returns `true` if the tree has no node elements.
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Returns the number of elements in the tree.
+    pub fn size(&self) -> usize {
+        self.tree.size()
+    }
+
+    pub fn contains(&self, key: usize) -> bool {
+        self.tree.contains(key)
+    }
+
+    /// Returns a clone of the value associated with the given key.
+    pub fn get(&self, key: usize) -> Option<V> {
+        self.tree.find(key).map(|node| node.get().value.value.clone())
+    }
+
+    /// Inserts the given key-value pair into the tree. If the tree already
+    /// contains the given key, the associated value is updated.
+    pub fn put(&mut self, key: usize, value: V) {
+        let path = self.tree.path_mut(|node| key.cmp(&node.key));
+        let parent = match path.last() {
+            Some(node) => node,
+            None => {
+                self.tree.root = Some(AugmentedBST::<V, M>::new_node(key, value));
+                return;
+            }
+        };
+
+        let parent_key = parent.get().key;
+        if key < parent_key {
+            parent.set_left(Some(AugmentedBST::<V, M>::new_node(key, value)));
+        } else if key > parent_key {
+            parent.set_right(Some(AugmentedBST::<V, M>::new_node(key, value)));
+        } else {
+            parent.0.borrow_mut().value.value = value;
+        }
+
+        // Even if only the value changed, the summaries above it did too.
+        for node in path.iter().rev() {
+            AugmentedBST::<V, M>::update(node);
+        }
+    }
+
+    fn new_node(key: usize, value: V) -> AugmentedNode<V, M> {
+        let summary = M::summarize(&value);
+        Node::new(key, Augmented { value, summary })
+    }
+
+    /// Updates the size and summary of the node from its children, like
+    /// `update_size` does for the size alone.
+    fn update(node: &AugmentedNode<V, M>) {
+        node.update_size();
+        let summary = AugmentedBST::<V, M>::node_summary(node);
+        node.0.borrow_mut().value.summary = summary;
+    }
+
+    /// Computes the summary of the subtree of `node` from its value and the
+    /// summaries stored in its children.
+    fn node_summary(node: &AugmentedNode<V, M>) -> M::Summary {
+        let raw = node.get();
+        let left = AugmentedBST::<V, M>::summary(&raw.left);
+        let right = AugmentedBST::<V, M>::summary(&raw.right);
+        M::combine(&M::combine(&left, &M::summarize(&raw.value.value)), &right)
+    }
+
+    /// Returns the summary stored in the node, or the identity if there is
+    /// no node.
+    fn summary(x: &Option<AugmentedNode<V, M>>) -> M::Summary {
+        match x {
+            Some(node) => node.get().value.summary.clone(),
+            None => M::identity(),
+        }
+    }
+
+    /// Removes the smallest key and its associated value from the tree.
+    pub fn delete_min(&mut self) {
+        let path = self.tree.path_mut(|_| Ordering::Less);
+        self.tree.unlink_with(path, AugmentedBST::<V, M>::update);
+    }
+
+    /// Removes the largest key and its associated value from the tree.
+    pub fn delete_max(&mut self) {
+        let path = self.tree.path_mut(|_| Ordering::Greater);
+        self.tree.unlink_with(path, AugmentedBST::<V, M>::update);
+    }
+
+    /// Removes the given key and its associated value from the tree.
+    pub fn delete(&mut self, key: usize) {
+        if !self.tree.contains(key) {
+            return;
+        }
+
+        let path = self.tree.path_mut(|node| key.cmp(&node.key));
+        self.tree.unlink_with(path, AugmentedBST::<V, M>::update);
+    }
+
+    /// Changes the value of the given key in place with `f`, in a single
+    /// walk down the tree. If the tree doesn't contain the key, `default` is
+    /// inserted for it first, or nothing happens if `default` is `None`.
+    ///
+    /// The key is removed if `f` returns `false`. Either way, the summaries
+    /// above it are updated.
+    pub fn modify<F>(&mut self, key: usize, default: Option<V>, f: F)
+        where F: FnOnce(&mut V) -> bool
+    {
+        let mut path = self.tree.path_mut(|node| key.cmp(&node.key));
+        if path.last().map(|node| node.get().key) != Some(key) {
+            let node = match default {
+                Some(value) => AugmentedBST::<V, M>::new_node(key, value),
+                None => return,
+            };
+            match path.last() {
+                Some(parent) if key < parent.get().key => parent.set_left(Some(node.clone())),
+                Some(parent) => parent.set_right(Some(node.clone())),
+                None => self.tree.root = Some(node.clone()),
+            }
+            path.push(node);
+        }
+
+        let keep = f(&mut path.last().unwrap().0.borrow_mut().value.value);
+        if keep {
+            for node in path.iter().rev() {
+                AugmentedBST::<V, M>::update(node);
+            }
+        } else {
+            self.tree.unlink_with(path, AugmentedBST::<V, M>::update);
+        }
+    }
+
+    /// Returns the summary of all values in the tree in O(1) time.
+    pub fn aggregate(&self) -> M::Summary {
+        AugmentedBST::<V, M>::summary(&self.tree.root)
+    }
+
+    /// Returns the summary of the values whose keys are in `lo..=hi`, in
+    /// O(height) time.
+    pub fn aggregate_range(&self, lo: usize, hi: usize) -> M::Summary {
+        // Find the highest node in the range. The paths to `lo` and `hi`
+        // split there.
+        let mut split = None;
+        let mut x = self.tree.root.clone();
+        while let Some(node) = x {
+            let key = node.get().key;
+            if hi < key {
+                x = node.get().left.clone();
+            } else if key < lo {
+                x = node.get().right.clone();
+            } else {
+                split = Some(node);
+                break;
+            }
+        }
+        let split = match split {
+            Some(node) => node,
+            None => return M::identity(),
+        };
+
+        // On the way to `lo`, every node in the range comes with its whole
+        // right subtree, and they all come before what was found so far.
+        let mut left = M::identity();
+        let mut x = split.get().left.clone();
+        while let Some(node) = x {
+            let raw = node.get();
+            if raw.key >= lo {
+                let right = AugmentedBST::<V, M>::summary(&raw.right);
+                let found = M::combine(&M::summarize(&raw.value.value), &right);
+                left = M::combine(&found, &left);
+                x = raw.left.clone();
+            } else {
+                x = raw.right.clone();
+            }
+        }
+
+        // On the way to `hi`, it is the same with the left subtrees, and
+        // they come after what was found so far.
+        let mut right = M::identity();
+        let mut x = split.get().right.clone();
+        while let Some(node) = x {
+            let raw = node.get();
+            if raw.key <= hi {
+                let left = AugmentedBST::<V, M>::summary(&raw.left);
+                let found = M::combine(&left, &M::summarize(&raw.value.value));
+                right = M::combine(&right, &found);
+                x = raw.right.clone();
+            } else {
+                x = raw.left.clone();
+            }
+        }
+
+        let middle = M::summarize(&split.get().value.value);
+        M::combine(&M::combine(&left, &middle), &right)
+    }
+
+    /// Returns the smallest key in the tree.
+    pub fn min(&self) -> Option<usize> {
+        self.tree.min()
+    }
+
+    /// Returns the largest key in the tree.
+    pub fn max(&self) -> Option<usize> {
+        self.tree.max()
+    }
+
+    /// Returns an iterator over the entries of the tree in key order.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, V)> {
+        self.tree.iter().map(|(key, augmented)| (key, augmented.value))
+    }
+
+    pub fn keys(&self) -> Vec<usize> {
+        self.tree.keys()
+    }
+}
+
+impl<V: Clone, M: Monoid<V>> AugmentedBST<V, M> where M::Summary: PartialEq {
+    /// Checks the ordering and `size` invariants of the tree, as in
+    /// `BST::check_invariants`, and that the summary stored in every node
+    /// matches its subtree.
+    pub fn check_invariants(&self) -> Result<(), InvariantViolation> {
+        self.tree.check_invariants()?;
+
+        let mut stack: Vec<AugmentedNode<V, M>> = self.tree.root.iter().cloned().collect();
+        while let Some(node) = stack.pop() {
+            if node.get().value.summary != AugmentedBST::<V, M>::node_summary(&node) {
+                return Err(InvariantViolation::Aggregate { key: node.get().key });
+            }
+            stack.extend(node.get().left.clone());
+            stack.extend(node.get().right.clone());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn range_sum() {
+        let mut tree: AugmentedBST<i64, Sum> = AugmentedBST::new();
+        assert_eq!(tree.aggregate(), 0);
+        assert_eq!(tree.aggregate_range(0, 100), 0);
+
+        for key in &[8, 3, 1, 7, 2, 5, 9, 6, 4] {
+            tree.put(*key, *key as i64 * 10);
+        }
+        assert_eq!(tree.aggregate(), 450);
+        assert_eq!(tree.aggregate_range(3, 6), 180);
+        assert_eq!(tree.aggregate_range(0, 4), 100);
+        assert_eq!(tree.aggregate_range(9, 20), 90);
+        assert_eq!(tree.aggregate_range(10, 20), 0);
+        assert_eq!(tree.aggregate_range(6, 3), 0);
+        assert_eq!(tree.check_invariants(), Ok(()));
+
+        tree.put(5, -50);
+        assert_eq!(tree.aggregate_range(3, 6), 80);
+        tree.delete(3);
+        assert_eq!(tree.aggregate_range(3, 6), 50);
+        tree.delete_min();
+        tree.delete_max();
+        assert_eq!(tree.aggregate(), 220);
+        assert_eq!(tree.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn range_max_and_min() {
+        let mut max: AugmentedBST<u32, Max> = AugmentedBST::new();
+        let mut min: AugmentedBST<u32, Min> = AugmentedBST::new();
+        for (key, value) in [(1, 5), (2, 9), (3, 2), (4, 7), (5, 1)] {
+            max.put(key, value);
+            min.put(key, value);
+        }
+
+        assert_eq!(max.aggregate_range(3, 5), Some(7));
+        assert_eq!(max.aggregate_range(1, 5), Some(9));
+        assert_eq!(max.aggregate_range(6, 9), None);
+        assert_eq!(min.aggregate_range(1, 4), Some(2));
+        assert_eq!(min.aggregate_range(4, 4), Some(7));
+
+        max.delete(2);
+        min.delete(5);
+        assert_eq!(max.aggregate(), Some(7));
+        assert_eq!(min.aggregate(), Some(2));
+    }
+
+    // Concatenates the keys, which shows that summaries are combined in
+    // key order.
+    struct Concat;
+
+    impl Monoid<char> for Concat {
+        type Summary = String;
+
+        fn identity() -> String {
+            String::new()
+        }
+
+        fn summarize(value: &char) -> String {
+            value.to_string()
+        }
+
+        fn combine(left: &String, right: &String) -> String {
+            format!("{}{}", left, right)
+        }
+    }
+
+    #[test]
+    fn custom_monoid() {
+        let mut tree: AugmentedBST<char, Concat> = AugmentedBST::new();
+        for (key, value) in [(4, 'd'), (2, 'b'), (6, 'f'), (1, 'a'), (3, 'c'), (5, 'e'), (7, 'g')] {
+            tree.put(key, value);
+        }
+
+        assert_eq!(tree.aggregate(), "abcdefg");
+        assert_eq!(tree.aggregate_range(2, 6), "bcdef");
+        assert_eq!(tree.aggregate_range(3, 3), "c");
+        assert_eq!(tree.iter().map(|(_, value)| value).collect::<String>(), "abcdefg");
+    }
+
+    #[test]
+    fn modify() {
+        let mut tree: AugmentedBST<i64, Sum> = AugmentedBST::new();
+        for key in test_util::KEYS {
+            tree.put(key, key as i64 * 10);
+        }
+
+        tree.modify(5, None, |value| {
+            *value += 1;
+            true
+        });
+        assert_eq!(tree.get(5), Some(51));
+        assert_eq!(tree.aggregate_range(4, 6), 151);
+
+        // absent keys are only inserted with a default
+        tree.modify(10, None, |_| true);
+        assert!(!tree.contains(10));
+        tree.modify(10, Some(0), |value| {
+            *value = 100;
+            true
+        });
+        assert_eq!(tree.get(10), Some(100));
+        assert_eq!(tree.aggregate(), 551);
+
+        tree.modify(3, None, |_| false);
+        tree.modify(11, Some(7), |_| false);
+        assert_eq!(tree.keys(), vec![1, 2, 4, 5, 6, 7, 8, 9, 10]);
+        assert_eq!(tree.aggregate(), 521);
+        assert_eq!(tree.check_invariants(), Ok(()));
+
+        let mut empty: AugmentedBST<i64, Sum> = AugmentedBST::new();
+        empty.modify(1, Some(5), |_| true);
+        assert_eq!(empty.aggregate(), 5);
+    }
+
+    #[test]
+    fn random_operations() {
+        type State = (AugmentedBST<u64, Sum>, BTreeMap<usize, u64>);
+        const KEYS: usize = 64;
+
+        test_util::random_operations(
+            20,
+            300,
+            |_| (AugmentedBST::new(), BTreeMap::new()),
+            &[
+                (3, |(tree, model): &mut State, rng| {
+                    let (key, value) = (rng.below(KEYS), rng.below(1000) as u64);
+                    tree.put(key, value);
+                    model.insert(key, value);
+                }),
+                (2, |(tree, model), rng| {
+                    let key = rng.below(KEYS);
+                    tree.delete(key);
+                    model.remove(&key);
+                }),
+                (1, |(tree, model), _| {
+                    tree.delete_min();
+                    model.pop_first();
+                }),
+                // adds to the value, and removes the key when it becomes a
+                // multiple of 4
+                (1, |(tree, model), rng| {
+                    let (key, add) = (rng.below(KEYS), rng.below(10) as u64);
+                    tree.modify(key, Some(0), |value| {
+                        *value += add;
+                        *value % 4 != 0
+                    });
+                    let value = model.get(&key).unwrap_or(&0) + add;
+                    if value % 4 != 0 {
+                        model.insert(key, value);
+                    } else {
+                        model.remove(&key);
+                    }
+                }),
+            ],
+            |(tree, model), rng| {
+                assert_eq!(tree.check_invariants(), Ok(()));
+
+                let lo = rng.below(KEYS);
+                let hi = lo + rng.below(16);
+                let sum: u64 = model.range(lo..=hi).map(|(_, v)| v).sum();
+                assert_eq!(tree.aggregate_range(lo, hi), sum);
+            },
+            |(tree, model)| {
+                assert_eq!(tree.iter().collect::<Vec<_>>(), model.into_iter().collect::<Vec<_>>());
+            },
+        );
+    }
+}
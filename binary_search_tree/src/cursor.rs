@@ -0,0 +1,399 @@
+use std::cmp::Ordering;
+use std::marker::PhantomData;
+
+use crate::{Node, RawNode, BST};
+
+/// A cursor over a `BST`.
+///
+/// A cursor points at one node of the tree and can move to the node's
+/// parent, its children, or the nodes before and after it in key order.
+/// Moves that have no node to go to return `false` and leave the cursor
+/// where it was. A cursor over an empty tree points at nothing.
+///
+/// Cursors are created with the `cursor_*` methods of `BST`.
+pub struct Cursor<'a, V: Clone> {
+    // Nodes from the root down to the current node.
+    path: Vec<Node<V>>,
+    tree: PhantomData<&'a BST<V>>,
+}
+
+/// A cursor over a `BST` that can modify the values of the tree.
+///
+/// It moves like a `Cursor`. The nodes it visits are made safe to modify on
+/// the way down, so changing a value never affects a snapshot of the tree.
+///
+/// Mutable cursors are created with the `cursor_*_mut` methods of `BST`.
+pub struct CursorMut<'a, V: Clone> {
+    // Nodes from the root down to the current node, all of them already
+    // safe to modify.
+    path: Vec<Node<V>>,
+    tree: PhantomData<&'a mut BST<V>>,
+}
+
+impl<V: Clone> Clone for Cursor<'_, V> {
+    fn clone(&self) -> Self {
+        Cursor { path: self.path.clone(), tree: PhantomData }
+    }
+}
+
+impl<'a, V: Clone> Cursor<'a, V> {
+    /// Returns the key of the node the cursor points at.
+    pub fn key(&self) -> Option<usize> {
+        self.path.last().map(|node| node.get().key)
+    }
+
+    /// Returns a reference to the value of the node the cursor points at.
+    pub fn value(&self) -> Option<&'a V> {
+        // SAFETY: the tree is borrowed for 'a.
+        self.path.last().map(|node| unsafe { node.value_ref() })
+    }
+
+    /// Moves the cursor to the parent of the current node.
+    pub fn move_parent(&mut self) -> bool {
+        move_parent(&mut self.path)
+    }
+
+    /// Moves the cursor to the left child of the current node.
+    pub fn move_left(&mut self) -> bool {
+        move_child(&mut self.path, Ordering::Less, child)
+    }
+
+    /// Moves the cursor to the right child of the current node.
+    pub fn move_right(&mut self) -> bool {
+        move_child(&mut self.path, Ordering::Greater, child)
+    }
+
+    /// Moves the cursor to the node with the next larger key.
+    pub fn move_next(&mut self) -> bool {
+        step(&mut self.path, Ordering::Greater, child)
+    }
+
+    /// Moves the cursor to the node with the next smaller key.
+    pub fn move_prev(&mut self) -> bool {
+        step(&mut self.path, Ordering::Less, child)
+    }
+}
+
+impl<'a, V: Clone> CursorMut<'a, V> {
+    /// Returns the key of the node the cursor points at.
+    pub fn key(&self) -> Option<usize> {
+        self.path.last().map(|node| node.get().key)
+    }
+
+    /// Returns a reference to the value of the node the cursor points at.
+    pub fn value(&self) -> Option<&V> {
+        // SAFETY: the cursor, which mutably borrows the tree, is borrowed for
+        // as long as the reference lives.
+        self.path.last().map(|node| unsafe { node.value_ref() })
+    }
+
+    /// Returns a mutable reference to the value of the node the cursor
+    /// points at.
+    pub fn value_mut(&mut self) -> Option<&mut V> {
+        // SAFETY: the path was made by `path_mut`, and the cursor is mutably
+        // borrowed for as long as the reference lives.
+        self.path.last().map(|node| unsafe { node.value_mut() })
+    }
+
+    /// Converts the cursor into a mutable reference to the value of the node
+    /// it points at, which lives as long as the borrow of the tree.
+    pub fn into_value_mut(self) -> Option<&'a mut V> {
+        // SAFETY: the path was made by `path_mut`, and the tree stays
+        // mutably borrowed for 'a.
+        self.path.last().map(|node| unsafe { node.value_mut() })
+    }
+
+    /// Moves the cursor to the parent of the current node.
+    pub fn move_parent(&mut self) -> bool {
+        move_parent(&mut self.path)
+    }
+
+    /// Moves the cursor to the left child of the current node.
+    pub fn move_left(&mut self) -> bool {
+        move_child(&mut self.path, Ordering::Less, unshared_child)
+    }
+
+    /// Moves the cursor to the right child of the current node.
+    pub fn move_right(&mut self) -> bool {
+        move_child(&mut self.path, Ordering::Greater, unshared_child)
+    }
+
+    /// Moves the cursor to the node with the next larger key.
+    pub fn move_next(&mut self) -> bool {
+        step(&mut self.path, Ordering::Greater, unshared_child)
+    }
+
+    /// Moves the cursor to the node with the next smaller key.
+    pub fn move_prev(&mut self) -> bool {
+        step(&mut self.path, Ordering::Less, unshared_child)
+    }
+}
+
+// `side` picks the left child with `Less` and the right child with `Greater`.
+
+fn child<V: Clone>(node: &Node<V>, side: Ordering) -> Option<Node<V>> {
+    match side {
+        Ordering::Less => node.get().left.clone(),
+        _ => node.get().right.clone(),
+    }
+}
+
+fn unshared_child<V: Clone>(node: &Node<V>, side: Ordering) -> Option<Node<V>> {
+    match side {
+        Ordering::Less => node.unshared_left(),
+        _ => node.unshared_right(),
+    }
+}
+
+fn move_parent<V: Clone>(path: &mut Vec<Node<V>>) -> bool {
+    if path.len() < 2 {
+        return false;
+    }
+    path.pop();
+    true
+}
+
+fn move_child<V: Clone, F>(path: &mut Vec<Node<V>>, side: Ordering, child: F) -> bool
+    where F: Fn(&Node<V>, Ordering) -> Option<Node<V>>
+{
+    match path.last().and_then(|node| child(node, side)) {
+        Some(node) => {
+            path.push(node);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Moves `path` to the node with the next key towards `side`: the next
+/// larger key for `Greater` and the next smaller key for `Less`.
+fn step<V: Clone, F>(path: &mut Vec<Node<V>>, side: Ordering, child: F) -> bool
+    where F: Fn(&Node<V>, Ordering) -> Option<Node<V>>
+{
+    let node = match path.last() {
+        Some(node) => node.clone(),
+        None => return false,
+    };
+
+    // With a subtree on that side, the next key is the closest one in it,
+    // found by going down that side once and then the other way.
+    if let Some(mut x) = child(&node, side) {
+        loop {
+            let next = child(&x, side.reverse());
+            path.push(x);
+            match next {
+                Some(node) => x = node,
+                None => return true,
+            }
+        }
+    }
+
+    // Otherwise it is the deepest ancestor on that side of the node.
+    let key = node.get().key;
+    match path.iter().rposition(|n| n.get().key.cmp(&key) == side) {
+        Some(i) => {
+            path.truncate(i + 1);
+            true
+        }
+        None => false,
+    }
+}
+
+impl<V: Clone> BST<V> {
+    /// Returns a cursor pointing at the root of the tree.
+    pub fn cursor_root(&self) -> Cursor<'_, V> {
+        self.cursor(|_| Ordering::Equal)
+    }
+
+    /// Returns a cursor pointing at the node with the smallest key.
+    pub fn cursor_min(&self) -> Cursor<'_, V> {
+        self.cursor(|_| Ordering::Less)
+    }
+
+    /// Returns a cursor pointing at the node with the largest key.
+    pub fn cursor_max(&self) -> Cursor<'_, V> {
+        self.cursor(|_| Ordering::Greater)
+    }
+
+    /// Returns a cursor pointing at the node with the given key, or `None`
+    /// if the key is not in the tree.
+    pub fn cursor_at(&self, key: usize) -> Option<Cursor<'_, V>> {
+        let cursor = self.cursor(|node| key.cmp(&node.key));
+        if cursor.key() == Some(key) {
+            Some(cursor)
+        } else {
+            None
+        }
+    }
+
+    /// Returns a mutable cursor pointing at the root of the tree.
+    pub fn cursor_root_mut(&mut self) -> CursorMut<'_, V> {
+        self.cursor_mut(|_| Ordering::Equal)
+    }
+
+    /// Returns a mutable cursor pointing at the node with the smallest key.
+    pub fn cursor_min_mut(&mut self) -> CursorMut<'_, V> {
+        self.cursor_mut(|_| Ordering::Less)
+    }
+
+    /// Returns a mutable cursor pointing at the node with the largest key.
+    pub fn cursor_max_mut(&mut self) -> CursorMut<'_, V> {
+        self.cursor_mut(|_| Ordering::Greater)
+    }
+
+    /// Returns a mutable cursor pointing at the node with the given key, or
+    /// `None` if the key is not in the tree.
+    pub fn cursor_at_mut(&mut self, key: usize) -> Option<CursorMut<'_, V>> {
+        // Look before walking so that a missing key doesn't copy nodes
+        // shared with a snapshot.
+        if !self.contains(key) {
+            return None;
+        }
+        Some(self.cursor_mut(|node| key.cmp(&node.key)))
+    }
+
+    /// Walks down from the root as in `path_mut`, without copying any node.
+    fn cursor<F>(&self, mut direction: F) -> Cursor<'_, V>
+        where F: FnMut(&RawNode<V>) -> Ordering
+    {
+        let mut path = Vec::new();
+        let mut x = self.root.clone();
+        while let Some(node) = x {
+            x = match direction(&node.get()) {
+                Ordering::Equal => None,
+                side => child(&node, side),
+            };
+            path.push(node);
+        }
+        Cursor { path, tree: PhantomData }
+    }
+
+    fn cursor_mut<F>(&mut self, direction: F) -> CursorMut<'_, V>
+        where F: FnMut(&RawNode<V>) -> Ordering
+    {
+        CursorMut { path: self.path_mut(direction), tree: PhantomData }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::populate_tree;
+
+    #[test]
+    fn move_around() {
+        let bst = populate_tree(|key| key * 10);
+
+        let mut cursor = bst.cursor_root();
+        assert_eq!(cursor.key(), Some(8));
+        assert_eq!(cursor.value(), Some(&80));
+        assert!(!cursor.move_parent());
+
+        assert!(cursor.move_left());
+        assert!(cursor.move_right());
+        assert!(cursor.move_left());
+        assert_eq!(cursor.key(), Some(5));
+        assert!(cursor.move_parent());
+        assert_eq!(cursor.key(), Some(7));
+        // a failed move leaves the cursor where it was
+        assert!(!cursor.move_right());
+        assert_eq!(cursor.key(), Some(7));
+
+        let mut cursor = bst.cursor_at(2).unwrap();
+        assert_eq!(cursor.value(), Some(&20));
+        assert!(!cursor.move_left());
+        assert!(!cursor.move_right());
+        assert!(cursor.move_parent());
+        assert_eq!(cursor.key(), Some(1));
+        assert!(cursor.move_parent());
+        assert!(cursor.move_parent());
+        assert_eq!(cursor.key(), Some(8));
+        assert!(!cursor.move_parent());
+
+        assert!(bst.cursor_at(10).is_none());
+    }
+
+    #[test]
+    fn move_next_and_prev() {
+        let bst = populate_tree(|key| key * 10);
+
+        let mut cursor = bst.cursor_min();
+        let mut keys = vec![cursor.key().unwrap()];
+        while cursor.move_next() {
+            keys.push(cursor.key().unwrap());
+        }
+        assert_eq!(keys, bst.keys());
+        assert_eq!(cursor.key(), Some(9));
+
+        let mut cursor = bst.cursor_max();
+        let mut keys = vec![cursor.key().unwrap()];
+        while cursor.move_prev() {
+            keys.push(cursor.key().unwrap());
+        }
+        keys.reverse();
+        assert_eq!(keys, bst.keys());
+        assert_eq!(cursor.key(), Some(1));
+
+        let mut cursor = bst.cursor_at(6).unwrap();
+        assert!(cursor.move_next());
+        assert_eq!(cursor.key(), Some(7));
+        let mut back = cursor.clone();
+        assert!(cursor.move_next());
+        assert_eq!(cursor.key(), Some(8));
+        assert!(back.move_prev());
+        assert!(back.move_prev());
+        assert_eq!(back.key(), Some(5));
+    }
+
+    #[test]
+    fn empty_tree() {
+        let mut bst: BST<usize> = BST::new();
+
+        let mut cursor = bst.cursor_root();
+        assert_eq!(cursor.key(), None);
+        assert_eq!(cursor.value(), None);
+        assert!(!cursor.move_next());
+        assert!(!cursor.move_prev());
+        assert!(!cursor.move_left());
+        assert!(!cursor.move_parent());
+
+        let mut cursor = bst.cursor_min_mut();
+        assert_eq!(cursor.value_mut(), None);
+        assert!(!cursor.move_next());
+        assert!(bst.cursor_at_mut(1).is_none());
+    }
+
+    #[test]
+    fn modify_values() {
+        let mut bst = populate_tree(|key| key * 10);
+        let snapshot = bst.snapshot();
+
+        let mut cursor = bst.cursor_min_mut();
+        loop {
+            *cursor.value_mut().unwrap() += 1;
+            if !cursor.move_next() {
+                break;
+            }
+        }
+        assert!(cursor.move_parent());
+        assert_eq!(cursor.key(), Some(8));
+        assert_eq!(cursor.value(), Some(&81));
+
+        let mut cursor = bst.cursor_root_mut();
+        assert!(cursor.move_left());
+        assert!(cursor.move_right());
+        *cursor.value_mut().unwrap() = 0;
+        *bst.cursor_at_mut(4).unwrap().into_value_mut().unwrap() = 40;
+        *bst.cursor_max_mut().into_value_mut().unwrap() *= 2;
+
+        assert_eq!(bst.iter().collect::<Vec<_>>(), vec![
+            (1, 11), (2, 21), (3, 31), (4, 40), (5, 51),
+            (6, 61), (7, 0), (8, 81), (9, 182),
+        ]);
+        assert_eq!(bst.check_invariants(), Ok(()));
+
+        // the snapshot still sees the old values
+        assert_eq!(snapshot.iter().collect::<Vec<_>>(),
+            (1..10).map(|key| (key, key * 10)).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,442 @@
+use std::error::Error;
+use std::fmt;
+use std::fmt::Write;
+
+use crate::{Node, BST};
+
+/// A broken invariant found by `BST::check_invariants`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvariantViolation {
+    /// The key of a node is out of the range that its ancestors allow: it
+    /// must be greater than `lower` and smaller than `upper`, if given. In a
+    /// `MultiBST` it may also be equal to `lower`.
+    Ordering {
+        key: usize,
+        lower: Option<usize>,
+        upper: Option<usize>,
+    },
+    /// The `size` stored in a node is not 1 plus the sizes stored in its
+    /// children.
+    Size {
+        key: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// The aggregate stored in a node of an `AugmentedBST` is not the
+    /// combination of its value and the aggregates stored in its children.
+    Aggregate {
+        key: usize,
+    },
+}
+
+impl fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvariantViolation::Ordering { key, lower, upper } => {
+                write!(f, "key {} is out of order, it must be", key)?;
+                if let Some(lower) = lower {
+                    write!(f, " greater than {}", lower)?;
+                }
+                if lower.is_some() && upper.is_some() {
+                    write!(f, " and")?;
+                }
+                if let Some(upper) = upper {
+                    write!(f, " smaller than {}", upper)?;
+                }
+                Ok(())
+            }
+            InvariantViolation::Size { key, expected, found } => {
+                write!(f, "node {} has size {} but its subtree has {} nodes",
+                    key, found, expected)
+            }
+            InvariantViolation::Aggregate { key } => {
+                write!(f, "node {} has an aggregate that doesn't match its subtree", key)
+            }
+        }
+    }
+}
+
+impl Error for InvariantViolation {}
+
+impl<V: Clone> BST<V> {
+    /// Checks that every key is greater than all keys in its left subtree and
+    /// smaller than all keys in its right subtree, and that the `size` of
+    /// every node is 1 plus the sizes of its children. Returns the first
+    /// violation found, in preorder.
+    pub fn check_invariants(&self) -> Result<(), InvariantViolation> {
+        self.check_with(false)
+    }
+
+    /// As `check_invariants`, but with `duplicates` a key may also be equal
+    /// to the keys in its right subtree, as in a `MultiBST`.
+    pub(crate) fn check_with(&self, duplicates: bool) -> Result<(), InvariantViolation> {
+        // (node, lower bound, exclusive upper bound)
+        let mut stack: Vec<(Node<V>, Option<usize>, Option<usize>)> = Vec::new();
+        stack.extend(self.root.clone().map(|root| (root, None, None)));
+        while let Some((node, lower, upper)) = stack.pop() {
+            let raw = node.get();
+            let key = raw.key;
+            let too_small = lower.is_some_and(|lower| key < lower || key == lower && !duplicates);
+            let too_large = upper.is_some_and(|upper| key >= upper);
+            if too_small || too_large {
+                return Err(InvariantViolation::Ordering { key, lower, upper });
+            }
+
+            let expected = 1 + Node::size(&raw.left) + Node::size(&raw.right);
+            if raw.size != expected {
+                return Err(InvariantViolation::Size { key, expected, found: raw.size });
+            }
+
+            stack.extend(raw.right.clone().map(|right| (right, Some(key), upper)));
+            stack.extend(raw.left.clone().map(|left| (left, lower, Some(key))));
+        }
+        Ok(())
+    }
+
+    /// Returns the number of nodes on the longest path from the root to a
+    /// leaf. An empty tree has height 0 and a single node has height 1.
+    pub fn height(&self) -> usize {
+        let mut height = 0;
+        self.visit_depths(|_, depth| height = height.max(depth + 1));
+        height
+    }
+
+    /// Returns the average depth of the nodes, where the root has depth 0.
+    ///
+    /// This is the average number of links followed by a successful search.
+    /// It is close to log2(n) for a balanced tree and (n - 1) / 2 for a tree
+    /// that has degenerated into a list. An empty tree returns 0.
+    pub fn average_depth(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+
+        let mut total = 0;
+        self.visit_depths(|_, depth| total += depth);
+        total as f64 / self.size() as f64
+    }
+
+    /// Calls `f` with every node and its depth, in preorder.
+    fn visit_depths<F: FnMut(&Node<V>, usize)>(&self, mut f: F) {
+        let mut stack: Vec<(Node<V>, usize)> = Vec::new();
+        stack.extend(self.root.clone().map(|root| (root, 0)));
+        while let Some((node, depth)) = stack.pop() {
+            f(&node, depth);
+            let raw = node.get();
+            stack.extend(raw.right.clone().map(|right| (right, depth + 1)));
+            stack.extend(raw.left.clone().map(|left| (left, depth + 1)));
+        }
+    }
+
+    /// Returns the shape of the tree in the Graphviz DOT language.
+    ///
+    /// Nodes are labelled with their keys. A missing child is drawn as a
+    /// point so that a lone child still shows which side it is on.
+    ///
+    /// ```text
+    /// $ dot -Tsvg tree.dot -o tree.svg
+    /// ```
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph BST {\n    node [shape=circle];\n");
+        let mut nulls = 0;
+        self.visit_depths(|node, _| {
+            let raw = node.get();
+            writeln!(dot, "    {};", raw.key).unwrap();
+            let left = raw.left.as_ref().map(|n| n.get().key);
+            let right = raw.right.as_ref().map(|n| n.get().key);
+            if left.is_none() && right.is_none() {
+                return;
+            }
+            for child in &[left, right] {
+                match child {
+                    Some(key) => writeln!(dot, "    {} -> {};", raw.key, key).unwrap(),
+                    None => {
+                        writeln!(dot, "    null{} [shape=point];", nulls).unwrap();
+                        writeln!(dot, "    {} -> null{};", raw.key, nulls).unwrap();
+                        nulls += 1;
+                    }
+                }
+            }
+        });
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Returns the shape of the tree drawn with ASCII characters, one node per
+    /// line, with the left child of each node listed before its right child.
+    ///
+    /// ```text
+    /// 8
+    /// +-- L: 3
+    /// |   +-- L: 1
+    /// |   |   `-- R: 2
+    /// |   `-- R: 7
+    /// `-- R: 9
+    /// ```
+    pub fn to_ascii(&self) -> String {
+        let mut ascii = String::new();
+        let root = match &self.root {
+            Some(root) => root.clone(),
+            None => return ascii,
+        };
+
+        writeln!(ascii, "{}", root.get().key).unwrap();
+        // (node, side, prefix of its line, whether it's the last child)
+        let mut stack = Vec::new();
+        BST::push_children(&mut stack, &root, "");
+        while let Some((node, side, prefix, last)) = stack.pop() {
+            let branch = if last { "`-- " } else { "+-- " };
+            writeln!(ascii, "{}{}{}: {}", prefix, branch, side, node.get().key).unwrap();
+
+            let indent = if last { "    " } else { "|   " };
+            BST::push_children(&mut stack, &node, &format!("{}{}", prefix, indent));
+        }
+        ascii
+    }
+
+    fn push_children(
+        stack: &mut Vec<(Node<V>, char, String, bool)>,
+        node: &Node<V>,
+        prefix: &str,
+    ) {
+        let raw = node.get();
+        if let Some(right) = &raw.right {
+            stack.push((right.clone(), 'R', prefix.to_string(), true));
+        }
+        if let Some(left) = &raw.left {
+            let last = raw.right.is_none();
+            stack.push((left.clone(), 'L', prefix.to_string(), last));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+    use crate::{Entry, RandomizedBST};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn height_and_average_depth() {
+        let mut bst = BST::new();
+        assert_eq!(bst.height(), 0);
+        assert_eq!(bst.average_depth(), 0.0);
+
+        bst.put(1, "1".to_string());
+        assert_eq!(bst.height(), 1);
+        assert_eq!(bst.average_depth(), 0.0);
+
+        let bst = test_util::populate_tree(|key| key.to_string());
+        assert_eq!(bst.height(), 5);
+        // depths: 8 -> 0, 3 9 -> 1, 1 7 -> 2, 2 5 -> 3, 4 6 -> 4
+        assert_eq!(bst.average_depth(), 20.0 / 9.0);
+
+        let chain: BST<usize> = (0..100).map(|key| (key, key)).collect();
+        assert_eq!(chain.height(), 100);
+        assert_eq!(chain.average_depth(), 49.5);
+
+        let balanced = BST::from_sorted((0..127).map(|key| (key, key)));
+        assert_eq!(balanced.height(), 7);
+    }
+
+    #[test]
+    fn to_ascii() {
+        let bst: BST<String> = BST::new();
+        assert_eq!(bst.to_ascii(), "");
+
+        let bst = test_util::populate_tree(|key| key.to_string());
+        assert_eq!(bst.to_ascii(), "\
+8
++-- L: 3
+|   +-- L: 1
+|   |   `-- R: 2
+|   `-- R: 7
+|       `-- L: 5
+|           +-- L: 4
+|           `-- R: 6
+`-- R: 9
+");
+    }
+
+    #[test]
+    fn to_dot() {
+        let mut bst = BST::new();
+        assert_eq!(bst.to_dot(), "digraph BST {\n    node [shape=circle];\n}\n");
+
+        bst.put(2, ());
+        bst.put(1, ());
+        bst.put(3, ());
+        bst.put(4, ());
+        assert_eq!(bst.to_dot(), "\
+digraph BST {
+    node [shape=circle];
+    2;
+    2 -> 1;
+    2 -> 3;
+    1;
+    3;
+    null0 [shape=point];
+    3 -> null0;
+    3 -> 4;
+    4;
+}
+");
+    }
+
+    #[test]
+    fn deep_tree() {
+        let n = 2000;
+        let mut chain = BST::new();
+        for key in (0..n).rev() {
+            chain.put(key, ());
+        }
+
+        assert_eq!(chain.height(), n);
+        assert_eq!(chain.average_depth(), (n - 1) as f64 / 2.0);
+        assert_eq!(chain.to_ascii().lines().count(), n);
+        assert_eq!(chain.to_dot().matches(" -> ").count(), 2 * (n - 1));
+
+        chain.rebalance();
+        assert_eq!(chain.height(), 11);
+    }
+
+    #[test]
+    fn check_invariants() {
+        let bst: BST<String> = BST::new();
+        assert_eq!(bst.check_invariants(), Ok(()));
+
+        let mut bst = test_util::populate_tree(|key| key.to_string());
+        assert_eq!(bst.check_invariants(), Ok(()));
+
+        // the root 8 holds all 9 nodes
+        let root = bst.root.clone().unwrap();
+        root.0.borrow_mut().size = 10;
+        let violation = InvariantViolation::Size { key: 8, expected: 9, found: 10 };
+        assert_eq!(bst.check_invariants(), Err(violation.clone()));
+        assert_eq!(violation.to_string(), "node 8 has size 10 but its subtree has 9 nodes");
+        root.update_size();
+        assert_eq!(bst.check_invariants(), Ok(()));
+
+        // 4 must stay between 3 and 5
+        let path = bst.path_mut(|node| 4.cmp(&node.key));
+        path.last().unwrap().0.borrow_mut().key = 10;
+        let violation = InvariantViolation::Ordering { key: 10, lower: Some(3), upper: Some(5) };
+        assert_eq!(bst.check_invariants(), Err(violation.clone()));
+        assert_eq!(violation.to_string(),
+            "key 10 is out of order, it must be greater than 3 and smaller than 5");
+
+        let violation = InvariantViolation::Ordering { key: 9, lower: None, upper: Some(8) };
+        assert_eq!(violation.to_string(), "key 9 is out of order, it must be smaller than 8");
+    }
+
+    // Property-based tests: random sequences of operations are applied to a
+    // tree and to a `BTreeMap` model, and the invariants are checked after
+    // every step. Each seed gives a different, reproducible sequence.
+
+    const KEYS: usize = 64;
+
+    fn check<V: Clone + PartialEq + fmt::Debug>(bst: &BST<V>, model: &BTreeMap<usize, V>) {
+        assert_eq!(bst.check_invariants(), Ok(()));
+        assert_eq!(bst.size(), model.len());
+        assert_eq!(bst.min(), model.keys().next().cloned());
+        assert_eq!(bst.max(), model.keys().next_back().cloned());
+    }
+
+    fn check_entries<V: Clone + PartialEq + fmt::Debug>(bst: &BST<V>, model: &BTreeMap<usize, V>) {
+        let entries: Vec<(usize, V)> = model.iter().map(|(k, v)| (*k, v.clone())).collect();
+        assert_eq!(bst.iter().collect::<Vec<_>>(), entries);
+    }
+
+    #[test]
+    fn random_operations() {
+        type Model = BTreeMap<usize, u32>;
+        type State = (BST<u32>, Model, Vec<(BST<u32>, Model)>);
+
+        test_util::random_operations(
+            50,
+            300,
+            |_| (BST::new(), BTreeMap::new(), Vec::new()),
+            &[
+                (3, |(bst, model, _): &mut State, rng| {
+                    let (key, value) = (rng.below(KEYS), rng.below(1000) as u32);
+                    bst.put(key, value);
+                    model.insert(key, value);
+                }),
+                (2, |(bst, model, _), rng| {
+                    let key = rng.below(KEYS);
+                    bst.delete(key);
+                    model.remove(&key);
+                }),
+                (1, |(bst, model, _), _| {
+                    bst.delete_min();
+                    model.pop_first();
+                }),
+                (1, |(bst, model, _), _| {
+                    bst.delete_max();
+                    model.pop_last();
+                }),
+                (1, |(bst, model, _), rng| {
+                    let key = rng.below(KEYS);
+                    match bst.entry(key) {
+                        Entry::Occupied(entry) if rng.below(2) == 0 => {
+                            assert_eq!(entry.remove(), model.remove(&key).unwrap());
+                        }
+                        entry => {
+                            *entry.or_insert(0) += 1;
+                            *model.entry(key).or_insert(0) += 1;
+                        }
+                    }
+                }),
+                (1, |(bst, model, _), rng| {
+                    let key = rng.below(KEYS);
+                    let mut right = bst.split_off(key);
+                    let mut model_right = model.split_off(&key);
+                    check(bst, model);
+                    check(&right, &model_right);
+                    bst.append(&mut right);
+                    model.append(&mut model_right);
+                }),
+                (1, |(bst, model, snapshots), _| snapshots.push((bst.snapshot(), model.clone()))),
+                (1, |(bst, _, _), _| bst.rebalance()),
+            ],
+            |(bst, model, _), _| check(bst, model),
+            |(bst, model, snapshots)| {
+                check_entries(&bst, &model);
+                for (snapshot, model) in &snapshots {
+                    check(snapshot, model);
+                    check_entries(snapshot, model);
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn random_operations_on_randomized_bst() {
+        type State = (RandomizedBST<usize>, BTreeMap<usize, usize>);
+
+        test_util::random_operations(
+            50,
+            300,
+            |seed| (RandomizedBST::with_seed(seed), BTreeMap::new()),
+            &[
+                (3, |(tree, model): &mut State, rng| {
+                    let (key, value) = (rng.below(KEYS), rng.below(1000));
+                    tree.put(key, value);
+                    model.insert(key, value);
+                }),
+                (2, |(tree, model), rng| {
+                    let key = rng.below(KEYS);
+                    tree.delete(key);
+                    model.remove(&key);
+                }),
+                (1, |(tree, model), _| {
+                    tree.delete_min();
+                    model.pop_first();
+                }),
+            ],
+            |(tree, model), _| check(&tree.tree, model),
+            |(tree, model)| check_entries(&tree.tree, &model),
+        );
+    }
+}
@@ -0,0 +1,350 @@
+use std::mem;
+
+use crate::{Node, BST};
+
+/// A view into a single entry in a tree, which may either be vacant or
+/// occupied.
+///
+/// This is constructed from the `entry` method on `BST`.
+pub enum Entry<'a, V: Clone> {
+    Vacant(VacantEntry<'a, V>),
+    Occupied(OccupiedEntry<'a, V>),
+}
+
+/// A view into a vacant entry in a `BST`. It is part of the `Entry` enum.
+pub struct VacantEntry<'a, V: Clone> {
+    key: usize,
+    tree: &'a mut BST<V>,
+    // Nodes from the root to the node that will be the parent of the new
+    // one, all of them already safe to modify.
+    path: Vec<Node<V>>,
+}
+
+/// A view into an occupied entry in a `BST`. It is part of the `Entry` enum.
+pub struct OccupiedEntry<'a, V: Clone> {
+    key: usize,
+    tree: &'a mut BST<V>,
+    // Nodes from the root to the node holding the key, all of them already
+    // safe to modify.
+    path: Vec<Node<V>>,
+}
+
+impl<'a, V: Clone> Entry<'a, V> {
+    pub(crate) fn new(tree: &'a mut BST<V>, key: usize) -> Self {
+        let path = tree.path_mut(|node| key.cmp(&node.key));
+        let found = match path.last() {
+            Some(node) => node.get().key == key,
+            None => false,
+        };
+
+        if found {
+            Entry::Occupied(OccupiedEntry { key, tree, path })
+        } else {
+            Entry::Vacant(VacantEntry { key, tree, path })
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the default if empty, and
+    /// returns a mutable reference to the value in the entry.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default),
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the result of the default
+    /// function if empty, and returns a mutable reference to the value in the
+    /// entry.
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    /// Like `or_insert_with`, but the default function is given the key.
+    pub fn or_insert_with_key<F: FnOnce(usize) -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let value = default(entry.key);
+                entry.insert(value)
+            }
+        }
+    }
+
+    /// Returns a reference to this entry's key.
+    pub fn key(&self) -> &usize {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    /// Provides in-place mutable access to an occupied entry before any
+    /// potential inserts into the tree.
+    pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        match self {
+            Entry::Occupied(mut entry) => {
+                f(entry.get_mut());
+                Entry::Occupied(entry)
+            }
+            Entry::Vacant(entry) => Entry::Vacant(entry),
+        }
+    }
+}
+
+impl<'a, V: Clone + Default> Entry<'a, V> {
+    /// Ensures a value is in the entry by inserting the default value if
+    /// empty, and returns a mutable reference to the value in the entry.
+    pub fn or_default(self) -> &'a mut V {
+        self.or_insert_with(Default::default)
+    }
+}
+
+impl<'a, V: Clone> VacantEntry<'a, V> {
+    /// Returns a reference to the key that would be used when inserting a
+    /// value through the `VacantEntry`.
+    pub fn key(&self) -> &usize {
+        &self.key
+    }
+
+    /// Takes ownership of the key.
+    pub fn into_key(self) -> usize {
+        self.key
+    }
+
+    /// Sets the value of the entry with the `VacantEntry`'s key, and returns a
+    /// mutable reference to it.
+    pub fn insert(self, value: V) -> &'a mut V {
+        let node = Node::new(self.key, value);
+        match self.path.last() {
+            Some(parent) => {
+                if self.key < parent.get().key {
+                    parent.set_left(Some(node.clone()));
+                } else {
+                    parent.set_right(Some(node.clone()));
+                }
+            }
+            None => self.tree.root = Some(node.clone()),
+        }
+        for n in self.path.iter().rev() {
+            n.update_size();
+        }
+
+        // SAFETY: the new node belongs to the tree, which stays mutably
+        // borrowed for 'a.
+        unsafe { node.value_mut() }
+    }
+}
+
+impl<'a, V: Clone> OccupiedEntry<'a, V> {
+    fn node(&self) -> &Node<V> {
+        self.path.last().unwrap()
+    }
+
+    /// Gets a reference to the key in the entry.
+    pub fn key(&self) -> &usize {
+        &self.key
+    }
+
+    /// Gets a reference to the value in the entry.
+    pub fn get(&self) -> &V {
+        // SAFETY: the entry, which mutably borrows the tree, is borrowed for
+        // as long as the reference lives.
+        unsafe { self.node().value_ref() }
+    }
+
+    /// Gets a mutable reference to the value in the entry.
+    ///
+    /// If you need a reference to the `OccupiedEntry` that may outlive the
+    /// destruction of the `Entry` value, see `into_mut`.
+    pub fn get_mut(&mut self) -> &mut V {
+        // SAFETY: the node comes from `path_mut`, and the entry is mutably
+        // borrowed for as long as the reference lives.
+        unsafe { self.node().value_mut() }
+    }
+
+    /// Converts the entry into a mutable reference to its value, which lives
+    /// as long as the borrow of the tree.
+    pub fn into_mut(self) -> &'a mut V {
+        // SAFETY: the node comes from `path_mut`, and the tree stays mutably
+        // borrowed for 'a.
+        unsafe { self.node().value_mut() }
+    }
+
+    /// Sets the value of the entry, and returns the entry's old value.
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+
+    /// Takes the value of the entry out of the tree, and returns it.
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    /// Takes the key-value pair of the entry out of the tree, and returns it.
+    pub fn remove_entry(self) -> (usize, V) {
+        let node = self.tree.unlink(self.path).unwrap();
+        (self.key, node.into_value())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[test]
+    fn counter() {
+        let mut bst = BST::new();
+        for key in &[5, 3, 8, 3, 5, 3, 1] {
+            *bst.entry(*key).or_insert(0) += 1;
+        }
+
+        assert_eq!(bst.keys(), vec![1, 3, 5, 8]);
+        assert_eq!(bst.get(3), Some(3));
+        assert_eq!(bst.get(5), Some(2));
+        assert_eq!(bst.get(1), Some(1));
+        assert_eq!(bst.size(), 4);
+    }
+
+    #[test]
+    fn or_insert_with() {
+        let mut bst = BST::new();
+        bst.put(2, "b".to_string());
+
+        let mut calls = 0;
+        bst.entry(2).or_insert_with(|| { calls += 1; "x".to_string() });
+        bst.entry(1).or_insert_with(|| { calls += 1; "a".to_string() });
+        bst.entry(3).or_insert_with_key(|key| key.to_string()).push('!');
+
+        assert_eq!(calls, 1);
+        assert_eq!(bst.get(1), Some("a".to_string()));
+        assert_eq!(bst.get(2), Some("b".to_string()));
+        assert_eq!(bst.get(3), Some("3!".to_string()));
+
+        let mut bst: BST<Vec<usize>> = BST::new();
+        bst.entry(4).or_default().push(1);
+        bst.entry(4).or_default().push(2);
+        assert_eq!(bst.get(4), Some(vec![1, 2]));
+    }
+
+    #[test]
+    fn and_modify() {
+        let mut bst = BST::new();
+        bst.entry(1).and_modify(|v| *v += 1).or_insert(10);
+        assert_eq!(bst.get(1), Some(10));
+        bst.entry(1).and_modify(|v| *v += 1).or_insert(10);
+        assert_eq!(bst.get(1), Some(11));
+    }
+
+    #[test]
+    fn occupied_and_vacant() {
+        let mut bst = BST::new();
+        for key in &[8, 3, 1, 7, 2, 5, 9, 6, 4] {
+            bst.put(*key, key * 10);
+        }
+
+        match bst.entry(5) {
+            Entry::Occupied(mut entry) => {
+                assert_eq!(entry.key(), &5);
+                assert_eq!(entry.get(), &50);
+                assert_eq!(entry.insert(55), 50);
+                *entry.get_mut() += 1;
+                assert_eq!(entry.get(), &56);
+            }
+            Entry::Vacant(_) => panic!("5 is in the tree"),
+        }
+        assert_eq!(bst.get(5), Some(56));
+
+        match bst.entry(10) {
+            Entry::Occupied(_) => panic!("10 is not in the tree"),
+            Entry::Vacant(entry) => {
+                assert_eq!(entry.key(), &10);
+                assert_eq!(*entry.insert(100), 100);
+            }
+        }
+        assert_eq!(bst.size(), 10);
+
+        // a vacant entry that is dropped leaves the tree unchanged
+        match bst.entry(0) {
+            Entry::Vacant(entry) => assert_eq!(entry.into_key(), 0),
+            Entry::Occupied(_) => panic!("0 is not in the tree"),
+        }
+        assert_eq!(bst.size(), 10);
+        assert!(!bst.contains(0));
+    }
+
+    #[test]
+    fn remove() {
+        let mut bst = BST::new();
+        for key in &[8, 3, 1, 7, 2, 5, 9, 6, 4] {
+            bst.put(*key, key * 10);
+        }
+
+        if let Entry::Occupied(entry) = bst.entry(3) {
+            assert_eq!(entry.remove_entry(), (3, 30));
+        }
+        if let Entry::Occupied(entry) = bst.entry(8) {
+            assert_eq!(entry.remove(), 80);
+        }
+
+        assert_eq!(bst.keys(), vec![1, 2, 4, 5, 6, 7, 9]);
+        assert_eq!(bst.size(), 7);
+    }
+
+    // A value that counts how many times it has been cloned
+    struct Counted(Rc<Cell<usize>>);
+
+    impl Clone for Counted {
+        fn clone(&self) -> Self {
+            self.0.set(self.0.get() + 1);
+            Counted(Rc::clone(&self.0))
+        }
+    }
+
+    #[test]
+    fn remove_moves_value_out() {
+        let clones = Rc::new(Cell::new(0));
+        let mut bst = BST::new();
+        for key in 0..10 {
+            bst.put(key, Counted(Rc::clone(&clones)));
+        }
+
+        if let Entry::Occupied(entry) = bst.entry(3) {
+            entry.remove();
+        }
+        assert_eq!(clones.get(), 0);
+
+        // the snapshot still holds the node of 5, so its value is copied
+        let snapshot = bst.snapshot();
+        if let Entry::Occupied(entry) = bst.entry(5) {
+            entry.remove();
+        }
+        assert!(clones.get() > 0);
+        assert!(snapshot.contains(5));
+        assert!(!bst.contains(5));
+    }
+
+    #[test]
+    fn entry_keeps_snapshot_unchanged() {
+        let mut bst = BST::new();
+        for key in 0..10 {
+            bst.put(key, key);
+        }
+        let snapshot = bst.snapshot();
+        let iter = bst.iter();
+
+        *bst.entry(9).or_insert(0) += 100;
+        *bst.entry(10).or_insert(0) += 100;
+
+        assert_eq!(bst.get(9), Some(109));
+        assert_eq!(bst.get(10), Some(100));
+        assert_eq!(snapshot.get(9), Some(9));
+        assert_eq!(snapshot.get(10), None);
+        assert_eq!(iter.last(), Some((9, 9)));
+    }
+}
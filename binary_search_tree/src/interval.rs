@@ -0,0 +1,341 @@
+use std::ops::Range;
+
+use crate::{AugmentedBST, InvariantViolation, Monoid};
+
+/// The ends of the intervals with the same start, and the largest of them.
+#[derive(Clone, Default)]
+struct Ends {
+    ends: Vec<usize>,
+    max: usize,
+}
+
+/// Keeps the largest end of the intervals in a subtree.
+struct MaxEnd;
+
+impl Monoid<Ends> for MaxEnd {
+    type Summary = Option<usize>;
+
+    fn identity() -> Option<usize> {
+        None
+    }
+
+    fn summarize(ends: &Ends) -> Option<usize> {
+        Some(ends.max)
+    }
+
+    fn combine(left: &Option<usize>, right: &Option<usize>) -> Option<usize> {
+        (*left).max(*right)
+    }
+}
+
+/// An interval tree of half-open `start..end` intervals.
+///
+/// Intervals are keyed by their start in an `AugmentedBST`, where every node
+/// holds the ends of the intervals with its start and stores the largest end
+/// in its subtree, the same way it stores its `size`. Subtrees that end
+/// before a query begins are skipped, so finding whether any interval
+/// overlaps a query takes O(height) time.
+///
+/// The same interval can be inserted more than once.
+pub struct IntervalTree {
+    tree: AugmentedBST<Ends, MaxEnd>,
+    size: usize,
+}
+
+impl Default for IntervalTree {
+    fn default() -> Self {
+        IntervalTree::new()
+    }
+}
+
+impl IntervalTree {
+    /// Creates a new empty interval tree.
+    pub fn new() -> Self {
+        IntervalTree {
+            tree: AugmentedBST::new(),
+            size: 0,
+        }
+    }
+
+    /// Returns `true` if the tree has no intervals.
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Returns the number of intervals in the tree.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn contains(&self, interval: Range<usize>) -> bool {
+        match self.tree.tree.find(interval.start) {
+            Some(node) => node.get().value.value.ends.contains(&interval.end),
+            None => false,
+        }
+    }
+
+    /// Inserts the given interval into the tree.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the interval is empty.
+    pub fn insert(&mut self, interval: Range<usize>) {
+        if interval.is_empty() {
+            panic!("interval must not be empty: {:?}", interval);
+        }
+
+        self.tree.modify(interval.start, Some(Ends::default()), |ends| {
+            ends.ends.push(interval.end);
+            ends.max = ends.max.max(interval.end);
+            true
+        });
+        self.size += 1;
+    }
+
+    /// Removes one occurrence of the given interval from the tree.
+    pub fn delete(&mut self, interval: Range<usize>) {
+        let mut removed = false;
+        self.tree.modify(interval.start, None, |ends| {
+            if let Some(i) = ends.ends.iter().position(|&end| end == interval.end) {
+                ends.ends.remove(i);
+                ends.max = ends.ends.iter().max().cloned().unwrap_or(0);
+                removed = true;
+            }
+            !ends.ends.is_empty()
+        });
+        if removed {
+            self.size -= 1;
+        }
+    }
+
+    /// Returns an interval that overlaps the given one, if there is any, in
+    /// O(height) time.
+    pub fn any_overlapping(&self, interval: Range<usize>) -> Option<Range<usize>> {
+        if interval.is_empty() {
+            return None;
+        }
+
+        let mut x = self.tree.tree.root.clone();
+        while let Some(node) = x {
+            let raw = node.get();
+            if raw.key < interval.end {
+                let end = raw.value.value.max;
+                if interval.start < end {
+                    return Some(raw.key..end);
+                }
+            }
+
+            // If the left subtree reaches past the start of the query but
+            // has no overlap, its intervals all start after the query ends,
+            // and so do those on the right.
+            let left_end = raw.left.as_ref().and_then(|left| left.get().value.summary);
+            x = if left_end.is_some_and(|end| interval.start < end) {
+                raw.left.clone()
+            } else {
+                raw.right.clone()
+            };
+        }
+        None
+    }
+
+    /// Returns all intervals that overlap the given one, ordered by start.
+    pub fn overlapping(&self, interval: Range<usize>) -> Vec<Range<usize>> {
+        let mut intervals = Vec::new();
+        if interval.is_empty() {
+            return intervals;
+        }
+
+        // An in-order walk that skips the subtrees ending before the query
+        // and stops at the first start after it.
+        let mut stack = Vec::new();
+        let mut x = self.tree.tree.root.clone();
+        loop {
+            while let Some(node) = x {
+                let end = node.get().value.summary;
+                if end.is_some_and(|end| end <= interval.start) {
+                    break;
+                }
+                x = node.get().left.clone();
+                stack.push(node);
+            }
+
+            let node = match stack.pop() {
+                Some(node) => node,
+                None => break,
+            };
+            let start = node.get().key;
+            if start >= interval.end {
+                break;
+            }
+            for &end in &node.get().value.value.ends {
+                if interval.start < end {
+                    intervals.push(start..end);
+                }
+            }
+            x = node.get().right.clone();
+        }
+        intervals
+    }
+
+    /// Returns all intervals that contain the given point, ordered by start.
+    pub fn containing(&self, point: usize) -> Vec<Range<usize>> {
+        match point.checked_add(1) {
+            Some(next) => self.overlapping(point..next),
+            // no interval ends after the largest point
+            None => Vec::new(),
+        }
+    }
+
+    /// Returns all intervals in the tree, ordered by start.
+    pub fn intervals(&self) -> Vec<Range<usize>> {
+        self.tree.iter()
+            .flat_map(|(start, ends)| ends.ends.into_iter().map(move |end| start..end))
+            .collect()
+    }
+
+    /// Checks the invariants of the underlying tree, including the largest
+    /// end stored in every node, as in `AugmentedBST::check_invariants`.
+    pub fn check_invariants(&self) -> Result<(), InvariantViolation> {
+        self.tree.check_invariants()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+    use crate::test_util;
+
+    fn populate_tree() -> IntervalTree {
+        let mut tree = IntervalTree::new();
+        for interval in [15..20, 10..30, 17..19, 5..20, 12..15, 30..40, 10..12] {
+            tree.insert(interval);
+        }
+        tree
+    }
+
+    #[test]
+    fn insert_and_delete() {
+        let mut tree = populate_tree();
+        assert_eq!(tree.size(), 7);
+        assert_eq!(tree.intervals(),
+            vec![5..20, 10..30, 10..12, 12..15, 15..20, 17..19, 30..40]);
+        assert!(tree.contains(10..12));
+        assert!(!tree.contains(10..13));
+        assert_eq!(tree.check_invariants(), Ok(()));
+
+        tree.insert(10..12);
+        assert_eq!(tree.size(), 8);
+        tree.delete(10..12);
+        tree.delete(10..30);
+        tree.delete(10..30);
+        tree.delete(11..30);
+        assert_eq!(tree.size(), 6);
+        assert!(tree.contains(10..12));
+        assert!(!tree.contains(10..30));
+        assert_eq!(tree.check_invariants(), Ok(()));
+
+        tree.delete(10..12);
+        tree.delete(30..40);
+        assert_eq!(tree.intervals(), vec![5..20, 12..15, 15..20, 17..19]);
+        assert_eq!(tree.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    #[should_panic(expected = "interval must not be empty")]
+    fn insert_empty() {
+        IntervalTree::new().insert(3..3);
+    }
+
+    #[test]
+    fn overlapping() {
+        let tree = populate_tree();
+
+        assert_eq!(tree.overlapping(14..16), vec![5..20, 10..30, 12..15, 15..20]);
+        assert_eq!(tree.overlapping(20..31), vec![10..30, 30..40]);
+        assert_eq!(tree.overlapping(0..5), vec![]);
+        assert_eq!(tree.overlapping(40..50), vec![]);
+        assert_eq!(tree.overlapping(18..18), vec![]);
+
+        assert_eq!(tree.containing(12), vec![5..20, 10..30, 12..15]);
+        assert_eq!(tree.containing(30), vec![30..40]);
+        assert_eq!(tree.containing(40), vec![]);
+        assert_eq!(tree.containing(usize::MAX), vec![]);
+    }
+
+    #[test]
+    fn any_overlapping() {
+        let mut tree = populate_tree();
+
+        assert!(tree.any_overlapping(14..16).is_some());
+        assert_eq!(tree.any_overlapping(35..36), Some(30..40));
+        assert_eq!(tree.any_overlapping(0..5), None);
+        assert_eq!(tree.any_overlapping(40..41), None);
+        assert_eq!(tree.any_overlapping(18..18), None);
+
+        tree.delete(30..40);
+        assert_eq!(tree.any_overlapping(35..36), None);
+        assert_eq!(tree.any_overlapping(29..36), Some(10..30));
+        assert_eq!(IntervalTree::new().any_overlapping(0..10), None);
+    }
+
+    #[test]
+    fn random_operations() {
+        type State = (IntervalTree, Vec<Range<usize>>);
+
+        fn random_interval(rng: &mut Rng) -> Range<usize> {
+            let start = rng.below(100);
+            start..start + 1 + rng.below(20)
+        }
+
+        test_util::random_operations(
+            20,
+            300,
+            |_| (IntervalTree::new(), Vec::new()),
+            &[
+                (3, |(tree, model): &mut State, rng| {
+                    let interval = random_interval(rng);
+                    tree.insert(interval.clone());
+                    model.push(interval);
+                }),
+                (2, |(tree, model), rng| {
+                    if !model.is_empty() {
+                        let interval = model.swap_remove(rng.below(model.len()));
+                        tree.delete(interval);
+                    }
+                }),
+                // mostly intervals that are not in the tree
+                (1, |(tree, model), rng| {
+                    let interval = random_interval(rng);
+                    tree.delete(interval.clone());
+                    if let Some(i) = model.iter().position(|x| *x == interval) {
+                        model.remove(i);
+                    }
+                }),
+            ],
+            |(tree, model), rng| {
+                assert_eq!(tree.check_invariants(), Ok(()));
+                assert_eq!(tree.size(), model.len());
+
+                let start = rng.below(120);
+                let query = start..start + rng.below(10);
+                let mut found = tree.overlapping(query.clone());
+                let mut expected: Vec<_> = model.iter()
+                    .filter(|x| !query.is_empty() && x.start < query.end && query.start < x.end)
+                    .cloned()
+                    .collect();
+                found.sort_by_key(|x| (x.start, x.end));
+                expected.sort_by_key(|x| (x.start, x.end));
+                assert_eq!(found, expected);
+                assert_eq!(tree.any_overlapping(query).is_some(), !expected.is_empty());
+            },
+            |(tree, model)| {
+                let mut expected = model;
+                expected.sort_by_key(|x| (x.start, x.end));
+                let mut found = tree.intervals();
+                found.sort_by_key(|x| (x.start, x.end));
+                assert_eq!(found, expected);
+            },
+        );
+    }
+}
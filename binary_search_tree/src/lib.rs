@@ -1,13 +1,57 @@
 use std::rc::Rc;
 use std::cell::RefCell;
 use std::cell::Ref;
+use std::cmp::Ordering;
 use std::collections::VecDeque;
-
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::iter::FromIterator;
+use std::ops::Index;
+
+mod augmented;
+mod cursor;
+mod diagnostics;
+mod entry;
+mod interval;
+mod multimap;
+mod randomized;
+mod rng;
+mod sync;
+#[cfg(test)]
+mod test_util;
+
+pub use augmented::{AugmentedBST, Max, Min, Monoid, Sum};
+pub use cursor::{Cursor, CursorMut};
+pub use diagnostics::InvariantViolation;
+pub use entry::{Entry, OccupiedEntry, VacantEntry};
+pub use interval::IntervalTree;
+pub use multimap::MultiBST;
+pub use randomized::RandomizedBST;
+pub use sync::SyncBST;
+
+// A node of a `BST`, shared between the tree, its snapshots and iterators.
+//
+// `value_ref` and `value_mut` hand out references to a value that outlive the
+// `RefCell` guard, which is sound because of this rule: a node is only
+// borrowed mutably by the one tree that owns it, through a mutable borrow of
+// that tree. A node that anything else may still reach, any node `unshared`
+// would copy, is never borrowed mutably, not even to change nothing. Such a
+// node is copied by `unshared` before a write, only read by `IntoIter`, and
+// left alone by `Drop`.
+//
+// So a reference to a value lives at most as long as a borrow of a tree that
+// reaches the node, and while that borrow lasts, nothing writes to the node.
+// Every `borrow_mut`, including those in `set_*` and `update_size`, must be
+// on a new node, a node returned by `unshared`, `unshared_left`,
+// `unshared_right` or `path_mut`, or a node owned alone: one whose strong
+// count is 1 and that is only referred to by a node owned alone, or by
+// nothing but the code at hand. A strong count of 1 is not enough by itself,
+// since the node's parent may still be shared.
 #[derive(Debug)]
-pub struct Node<V: Clone>(Rc<RefCell<RawNode<V>>>);
+struct Node<V: Clone>(Rc<RefCell<RawNode<V>>>);
 
 impl<V: Clone> Node<V> {
-    pub fn new(key: usize, value: V) -> Self {
+    fn new(key: usize, value: V) -> Self {
         let node = Rc::new(RefCell::new(RawNode {
             key,
             value,
@@ -18,38 +62,147 @@ impl<V: Clone> Node<V> {
         Node(node)
     }
 
-    pub fn get(&self) -> Ref<RawNode<V>> {
+    fn get(&self) -> Ref<'_, RawNode<V>> {
         self.0.borrow()
     }
 
-    pub fn set_value(&self, value: V) {
+    fn set_value(&self, value: V) {
         self.0.borrow_mut().value = value;
     }
 
-    pub fn set_left(&self, node: Option<Node<V>>) {
+    fn set_left(&self, node: Option<Node<V>>) {
         self.0.borrow_mut().left = node;
     }
 
-    pub fn set_right(&self, node: Option<Node<V>>) {
+    fn set_right(&self, node: Option<Node<V>>) {
         self.0.borrow_mut().right = node;
     }
 
-    pub fn update_size(&self) {
+    fn update_size(&self) {
         let size = 1 + Node::size(&self.get().left) + Node::size(&self.get().right);
         self.0.borrow_mut().size = size;
     }
 
-    pub fn clone(&self) -> Self {
-        Node(Rc::clone(&self.0))
+    /// Returns a node that can be modified without affecting any other tree.
+    ///
+    /// If this node is only referenced from one place, the node itself is
+    /// returned. Otherwise it is shared with a snapshot, so a copy is made
+    /// which shares the same children as the original.
+    fn unshared(&self) -> Self {
+        if Rc::strong_count(&self.0) == 1 {
+            return self.clone();
+        }
+
+        let raw = self.get();
+        Node(Rc::new(RefCell::new(RawNode {
+            key: raw.key,
+            value: raw.value.clone(),
+            left: raw.left.clone(),
+            right: raw.right.clone(),
+            size: raw.size,
+        })))
+    }
+
+    /// Takes the value out of a node that is no longer in the tree. The value
+    /// is only cloned if a snapshot still holds the node.
+    fn into_value(self) -> V {
+        let rc = Rc::clone(&self.0);
+        drop(self);
+        match Rc::try_unwrap(rc) {
+            Ok(cell) => cell.into_inner().value,
+            Err(rc) => rc.borrow().value.clone(),
+        }
+    }
+
+    /// Returns a reference to the value that isn't tied to a `Ref` guard.
+    ///
+    /// # Safety
+    ///
+    /// A tree that reaches the node must be borrowed for `'a`, as described
+    /// on `Node`.
+    unsafe fn value_ref<'a>(&self) -> &'a V {
+        &(*self.0.as_ptr()).value
+    }
+
+    /// Returns a mutable reference to the value that isn't tied to a `RefMut`
+    /// guard.
+    ///
+    /// # Safety
+    ///
+    /// The tree that owns the node must be mutably borrowed for `'a`, as
+    /// described on `Node`.
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn value_mut<'a>(&self) -> &'a mut V {
+        &mut (*self.0.as_ptr()).value
     }
 
-    pub fn size(node: &Option<Node<V>>) -> usize {
+    /// Makes the left child safe to modify, as in `unshared`, and returns it.
+    fn unshared_left(&self) -> Option<Node<V>> {
+        let left = self.get().left.as_ref()?.unshared();
+        self.set_left(Some(left.clone()));
+        Some(left)
+    }
+
+    /// Makes the right child safe to modify, as in `unshared`, and returns it.
+    fn unshared_right(&self) -> Option<Node<V>> {
+        let right = self.get().right.as_ref()?.unshared();
+        self.set_right(Some(right.clone()));
+        Some(right)
+    }
+
+    /// Replaces the child `old` of this node with `new`.
+    fn replace_child(&self, old: &Node<V>, new: Option<Node<V>>) {
+        let is_left = match &self.get().left {
+            Some(left) => Rc::ptr_eq(&left.0, &old.0),
+            None => false,
+        };
+        if is_left {
+            self.set_left(new);
+        } else {
+            self.set_right(new);
+        }
+    }
+
+    fn size(node: &Option<Node<V>>) -> usize {
         node.as_ref().map_or(0, |n| n.0.borrow().size)
     }
 }
 
+impl<V: Clone> Drop for Node<V> {
+    fn drop(&mut self) {
+        if Rc::strong_count(&self.0) > 1 {
+            return;
+        }
+
+        // Letting the children drop recursively overflows the stack on a
+        // degenerate tree, so detach the children of every node that is
+        // about to be freed and drop them one at a time instead.
+        let mut stack = Vec::new();
+        {
+            let mut raw = self.0.borrow_mut();
+            stack.extend(raw.left.take());
+            stack.extend(raw.right.take());
+        }
+        while let Some(node) = stack.pop() {
+            if Rc::strong_count(&node.0) == 1 {
+                let mut raw = node.0.borrow_mut();
+                stack.extend(raw.left.take());
+                stack.extend(raw.right.take());
+            }
+        }
+    }
+}
+
+impl<V: Clone> Clone for Node<V> {
+    /// Returns another reference to the same node. The underlying node is
+    /// shared, not copied.
+    fn clone(&self) -> Self {
+        Node(Rc::clone(&self.0))
+    }
+}
+
 #[derive(Debug)]
-pub struct RawNode<V: Clone> {
+struct RawNode<V: Clone> {
     key: usize,
     value: V,
     left: Option<Node<V>>,
@@ -63,9 +216,30 @@ pub struct TreeIter<V: Clone> {
 
 impl<V: Clone> TreeIter<V> {
     fn push_left_edge(&mut self, x: &Option<Node<V>>) {
-        if let Some(ref node) = *x {
-            self.unvisited.push(node.clone());
-            self.push_left_edge(&node.get().left);
+        let mut x = x.clone();
+        while let Some(node) = x {
+            x = node.get().left.clone();
+            self.unvisited.push(node);
+        }
+    }
+
+    /// Pushes the nodes of subtree `x` so that the next entry returned is
+    /// the one at position `rank` of the subtree. Nothing is pushed if
+    /// `rank` is not smaller than the size of the subtree.
+    fn seek(&mut self, x: &Option<Node<V>>, mut rank: usize) {
+        let mut x = x.clone();
+        while let Some(node) = x {
+            let left_size = Node::size(&node.get().left);
+            if rank < left_size {
+                x = node.get().left.clone();
+                self.unvisited.push(node);
+            } else if rank == left_size {
+                self.unvisited.push(node);
+                return;
+            } else {
+                rank -= left_size + 1;
+                x = node.get().right.clone();
+            }
         }
     }
 }
@@ -74,10 +248,7 @@ impl<V: Clone> Iterator for TreeIter<V> {
     type Item = (usize, V);
 
     fn next(&mut self) -> Option<(usize, V)> {
-        let node = match self.unvisited.pop() {
-            Some(n) => n,
-            None => return None,
-        };
+        let node = self.unvisited.pop()?;
 
         self.push_left_edge(&node.get().right);
 
@@ -85,19 +256,302 @@ impl<V: Clone> Iterator for TreeIter<V> {
         let value = node.get().value.clone();
         Some((key, value))
     }
+
+    /// Returns the `n`th next entry in O(height) time, skipping whole
+    /// subtrees by their sizes instead of visiting the entries in between.
+    fn nth(&mut self, mut n: usize) -> Option<(usize, V)> {
+        // Every node on the stack comes before its right subtree and both
+        // come before the rest of the stack.
+        while let Some(node) = self.unvisited.pop() {
+            let right = node.get().right.clone();
+            if n == 0 {
+                self.push_left_edge(&right);
+                let key = node.get().key;
+                let value = node.get().value.clone();
+                return Some((key, value));
+            }
+
+            let right_size = Node::size(&right);
+            if n <= right_size {
+                self.seek(&right, n - 1);
+                return self.next();
+            }
+            n -= 1 + right_size;
+        }
+        None
+    }
 }
 
-#[derive(Debug)]
+/// An owning iterator over the entries of a `BST`, in key order.
+///
+/// Values are moved out of the nodes the tree owns alone, and cloned from
+/// nodes still shared with a snapshot.
+pub struct IntoIter<V: Clone> {
+    unvisited: Vec<Node<V>>,
+}
+
+impl<V: Clone> IntoIter<V> {
+    fn push_left_edge(&mut self, x: Option<Node<V>>) {
+        let mut x = x;
+        while let Some(node) = x {
+            x = IntoIter::take_child(&node, Ordering::Less);
+            self.unvisited.push(node);
+        }
+    }
+
+    // Detaches the child from a node nothing else refers to, so that the
+    // child ends up uniquely owned as well. A node still shared with a
+    // snapshot is only read, since the snapshot may be borrowed from.
+    fn take_child(node: &Node<V>, side: Ordering) -> Option<Node<V>> {
+        if Rc::strong_count(&node.0) > 1 {
+            let raw = node.get();
+            return match side {
+                Ordering::Less => raw.left.clone(),
+                _ => raw.right.clone(),
+            };
+        }
+
+        let mut raw = node.0.borrow_mut();
+        match side {
+            Ordering::Less => raw.left.take(),
+            _ => raw.right.take(),
+        }
+    }
+}
+
+impl<V: Clone> Iterator for IntoIter<V> {
+    type Item = (usize, V);
+
+    fn next(&mut self) -> Option<(usize, V)> {
+        let node = self.unvisited.pop()?;
+
+        let right = IntoIter::take_child(&node, Ordering::Greater);
+        self.push_left_edge(right);
+
+        let key = node.get().key;
+        Some((key, node.into_value()))
+    }
+}
+
+/// A Binary Search Tree with copy-on-write nodes.
+///
+/// Nodes are shared between a tree and its snapshots. Operations that modify
+/// the tree copy the nodes on the path they change if those nodes are shared,
+/// so a snapshot never sees writes made after it was taken.
+///
+/// All operations are iterative, so a degenerate tree (for example one built
+/// from sorted keys) is slow but never overflows the stack.
 pub struct BST<V: Clone> {
     root: Option<Node<V>>,
 }
 
+impl<V: Clone> Default for BST<V> {
+    fn default() -> Self {
+        BST::new()
+    }
+}
+
+impl<V: Clone> Clone for BST<V> {
+    /// Returns a deep copy of the tree, which shares no nodes with it.
+    ///
+    /// Use `snapshot` for an O(1) copy that shares the nodes until either
+    /// tree is modified.
+    fn clone(&self) -> Self {
+        let root = match &self.root {
+            Some(node) => node,
+            None => return BST::new(),
+        };
+
+        let copy_of = |node: &Node<V>| {
+            let raw = node.get();
+            let copy = Node::new(raw.key, raw.value.clone());
+            copy.0.borrow_mut().size = raw.size;
+            copy
+        };
+
+        let new_root = copy_of(root);
+        let mut stack = vec![(root.clone(), new_root.clone())];
+        while let Some((node, copy)) = stack.pop() {
+            if let Some(left) = &node.get().left {
+                let new_left = copy_of(left);
+                copy.set_left(Some(new_left.clone()));
+                stack.push((left.clone(), new_left));
+            }
+            if let Some(right) = &node.get().right {
+                let new_right = copy_of(right);
+                copy.set_right(Some(new_right.clone()));
+                stack.push((right.clone(), new_right));
+            }
+        }
+
+        BST { root: Some(new_root) }
+    }
+}
+
+impl<V: Clone + fmt::Debug> fmt::Debug for BST<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<V: Clone + PartialEq> PartialEq for BST<V> {
+    fn eq(&self, other: &BST<V>) -> bool {
+        self.size() == other.size() && self.iter().eq(other.iter())
+    }
+}
+
+impl<V: Clone + Eq> Eq for BST<V> {}
+
+impl<V: Clone + Hash> Hash for BST<V> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.size().hash(state);
+        for entry in self.iter() {
+            entry.hash(state);
+        }
+    }
+}
+
+impl<V: Clone> FromIterator<(usize, V)> for BST<V> {
+    fn from_iter<I: IntoIterator<Item = (usize, V)>>(iter: I) -> Self {
+        let mut bst = BST::new();
+        bst.extend(iter);
+        bst
+    }
+}
+
+impl<V: Clone, const N: usize> From<[(usize, V); N]> for BST<V> {
+    fn from(entries: [(usize, V); N]) -> Self {
+        IntoIterator::into_iter(entries).collect()
+    }
+}
+
+impl<V: Clone> Extend<(usize, V)> for BST<V> {
+    fn extend<I: IntoIterator<Item = (usize, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.put(key, value);
+        }
+    }
+}
+
+impl<V: Clone> IntoIterator for BST<V> {
+    type Item = (usize, V);
+    type IntoIter = IntoIter<V>;
+
+    fn into_iter(mut self) -> IntoIter<V> {
+        let mut iter = IntoIter { unvisited: Vec::new() };
+        iter.push_left_edge(self.root.take());
+        iter
+    }
+}
+
+impl<V: Clone> IntoIterator for &BST<V> {
+    type Item = (usize, V);
+    type IntoIter = TreeIter<V>;
+
+    fn into_iter(self) -> TreeIter<V> {
+        self.iter()
+    }
+}
+
+impl<V: Clone> Index<usize> for BST<V> {
+    type Output = V;
+
+    /// Returns a reference to the value of the given key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is not present in the tree.
+    fn index(&self, key: usize) -> &V {
+        let node = self.find(key).expect("no entry found for key");
+        // SAFETY: the tree is borrowed for as long as the reference lives.
+        unsafe { node.value_ref() }
+    }
+}
+
+impl<V: Clone> Index<&usize> for BST<V> {
+    type Output = V;
+
+    /// Returns a reference to the value of the given key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is not present in the tree.
+    fn index(&self, key: &usize) -> &V {
+        &self[*key]
+    }
+}
+
 impl<V: Clone> BST<V> {
     /// Creates a new empty Binary Search Tree.
     pub fn new() -> Self {
         BST { root: None }
     }
 
+    /// Returns a snapshot of the tree in O(1) time.
+    ///
+    /// The snapshot shares all of its nodes with the tree. Later writes to
+    /// either of them path-copy the shared nodes, so neither sees the
+    /// changes made to the other.
+    pub fn snapshot(&self) -> Self {
+        BST { root: self.root.clone() }
+    }
+
+    /// Builds a perfectly balanced tree from entries sorted by key in O(n)
+    /// time.
+    ///
+    /// If a key appears more than once, the last of its values is kept.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the keys are not in ascending order.
+    pub fn from_sorted<I>(iter: I) -> Self
+        where I: IntoIterator<Item = (usize, V)>
+    {
+        let mut entries: Vec<(usize, V)> = Vec::new();
+        for (key, value) in iter {
+            match entries.last_mut() {
+                Some(last) if last.0 == key => last.1 = value,
+                Some(last) if last.0 > key => {
+                    panic!("keys must be in ascending order: {} came after {}", key, last.0);
+                }
+                _ => entries.push((key, value)),
+            }
+        }
+
+        let n = entries.len();
+        BST { root: BST::build(n, &mut entries.into_iter()) }
+    }
+
+    /// Rebuilds the tree in O(n) time so that it is perfectly balanced.
+    ///
+    /// Snapshots of the tree are not affected.
+    pub fn rebalance(&mut self) {
+        let n = self.size();
+        let mut iter = std::mem::take(self).into_iter();
+        self.root = BST::build(n, &mut iter);
+    }
+
+    /// Builds a perfectly balanced subtree out of the next `n` entries of
+    /// `iter`, which must be in ascending order of keys.
+    fn build<I>(n: usize, iter: &mut I) -> Option<Node<V>>
+        where I: Iterator<Item = (usize, V)>
+    {
+        if n == 0 {
+            return None;
+        }
+
+        // The recursion only goes as deep as the height of the new tree,
+        // which is about log2(n).
+        let left_size = (n - 1) / 2;
+        let left = BST::build(left_size, iter);
+        let (key, value) = iter.next().unwrap();
+        let node = Node::new(key, value);
+        node.set_left(left);
+        node.set_right(BST::build(n - 1 - left_size, iter));
+        node.update_size();
+        Some(node)
+    }
+
     /// Returns `true` if the tree has no node elements.
     pub fn is_empty(&self) -> bool {
         Node::size(&self.root) == 0
@@ -109,126 +563,246 @@ impl<V: Clone> BST<V> {
     }
 
     pub fn contains(&self, key: usize) -> bool {
-        self.get(key).is_some()
+        self.find(key).is_some()
     }
 
     /// Returns a clone of the value associated with the given key.
     pub fn get(&self, key: usize) -> Option<V> {
-        BST::get_value(&self.root, key)
+        self.find(key).map(|node| node.get().value.clone())
     }
 
-    fn get_value(x: &Option<Node<V>>, key: usize) -> Option<V> {
-        if let Some(node) = x {
-            if key < node.get().key {
-                return BST::get_value(&node.get().left, key);
-            } else if key > node.get().key {
-                return BST::get_value(&node.get().right, key);
+    fn find(&self, key: usize) -> Option<Node<V>> {
+        let mut x = self.root.clone();
+        while let Some(node) = x {
+            let node_key = node.get().key;
+            if key < node_key {
+                x = node.get().left.clone();
+            } else if key > node_key {
+                x = node.get().right.clone();
             } else {
-                return Some(node.get().value.clone());
+                return Some(node);
             }
         }
         None
     }
 
+    /// Walks down from the root and returns the nodes on the way, making
+    /// each of them safe to modify first.
+    ///
+    /// `direction` is called on every node: `Less` continues with the left
+    /// child, `Greater` with the right child and `Equal` stops the walk. The
+    /// walk also stops when the chosen child doesn't exist, so the last node
+    /// of the path is where the walk ended.
+    fn path_mut<F>(&mut self, mut direction: F) -> Vec<Node<V>>
+        where F: FnMut(&RawNode<V>) -> Ordering
+    {
+        let mut path = Vec::new();
+        let mut x = match &self.root {
+            Some(root) => root.unshared(),
+            None => return path,
+        };
+        self.root = Some(x.clone());
+
+        loop {
+            let ordering = direction(&x.get());
+            let next = match ordering {
+                Ordering::Less => x.unshared_left(),
+                Ordering::Greater => x.unshared_right(),
+                Ordering::Equal => None,
+            };
+            path.push(x);
+            match next {
+                Some(node) => x = node,
+                None => return path,
+            }
+        }
+    }
+
+    /// Removes the last node of `path` from the tree, where `path` is the
+    /// result of `path_mut`, and updates the size of every node above it.
+    /// Returns the removed node.
+    fn unlink(&mut self, path: Vec<Node<V>>) -> Option<Node<V>> {
+        self.unlink_with(path, Node::update_size)
+    }
+
+    /// As `unlink`, but calls `update` instead of `update_size` on every
+    /// node whose children changed, from the bottom up, so that fields
+    /// other than `size` can be kept up to date as well.
+    fn unlink_with<F: Fn(&Node<V>)>(&mut self, mut path: Vec<Node<V>>, update: F) -> Option<Node<V>> {
+        let node = path.pop()?;
+
+        let replacement = if node.get().right.is_none() {
+            node.get().left.clone()
+        } else if node.get().left.is_none() {
+            node.get().right.clone()
+        } else {
+            // minimum of the right replaces node to be deleted
+            let mut min_path = Vec::new();
+            let mut min = node.unshared_right().unwrap();
+            while let Some(left) = min.unshared_left() {
+                min_path.push(min);
+                min = left;
+            }
+            let parent = min_path.last().unwrap_or(&node);
+            parent.replace_child(&min, min.get().right.clone());
+            for n in min_path.iter().rev() {
+                update(n);
+            }
+
+            // new node takes left and right of the deleted
+            min.set_left(node.get().left.clone());
+            min.set_right(node.get().right.clone());
+            update(&min);
+            Some(min)
+        };
+
+        match path.last() {
+            Some(parent) => parent.replace_child(&node, replacement),
+            None => self.root = replacement,
+        }
+        for n in path.iter().rev() {
+            update(n);
+        }
+        Some(node)
+    }
+
     /// Inserts the given key-value pair into the tree. If the tree already
     /// contains the given key, the associated value is updated.
     pub fn put(&mut self, key: usize, value: V) {
-        self.root = BST::upsert(&self.root, key, value);
-    }
-
-    fn upsert(x: &Option<Node<V>>, key: usize, value: V) -> Option<Node<V>> {
-        if let Some(node) = x {
-            if key < node.get().key {
-                let new_node = BST::upsert(&node.get().left, key, value);
-                node.set_left(new_node);
-            } else if key > node.get().key {
-                let new_node = BST::upsert(&node.get().right, key, value);
-                node.set_right(new_node);
-            } else {
-                node.set_value(value);
+        let path = self.path_mut(|node| key.cmp(&node.key));
+        let parent = match path.last() {
+            Some(node) => node,
+            None => {
+                self.root = Some(Node::new(key, value));
+                return;
             }
+        };
+
+        let parent_key = parent.get().key;
+        if key < parent_key {
+            parent.set_left(Some(Node::new(key, value)));
+        } else if key > parent_key {
+            parent.set_right(Some(Node::new(key, value)));
+        } else {
+            parent.set_value(value);
+            return;
+        }
+
+        for node in path.iter().rev() {
             node.update_size();
-            return Some(node.clone());
         }
-        // x = None
-        Some(Node::new(key, value))
     }
 
-    /// Removes the smallest key and its associated value from the tree.
-    pub fn delete_min(&mut self) {
-        self.root = BST::remove_min(&self.root);
+    /// Gets the given key's corresponding entry in the tree for in-place
+    /// manipulation.
+    ///
+    /// The tree is only walked once, whether the entry is then read, updated,
+    /// filled in or removed.
+    pub fn entry(&mut self, key: usize) -> Entry<'_, V> {
+        Entry::new(self, key)
     }
 
-    fn remove_min(x: &Option<Node<V>>) -> Option<Node<V>> {
-        if let Some(node) = x {
-            if node.get().left.is_none() {
-                return node.get().right.as_ref().map(|n| n.clone());
-            }
-            let new_node = BST::remove_min(&node.get().left);
-            node.set_left(new_node);
-            node.update_size();
-            return Some(node.clone());
-        }
-        None
+    /// Removes the smallest key and its associated value from the tree.
+    pub fn delete_min(&mut self) {
+        let path = self.path_mut(|_| Ordering::Less);
+        self.unlink(path);
     }
 
     /// Removes the largest key and its associated value from the tree.
     pub fn delete_max(&mut self) {
-        self.root = BST::remove_max(&self.root);
-    }
-
-    fn remove_max(x: &Option<Node<V>>) -> Option<Node<V>> {
-        if let Some(node) = x {
-            if node.get().right.is_none() {
-                return node.get().left.as_ref().map(|n| n.clone());
-            }
-            let new_node = BST::remove_max(&node.get().right);
-            node.set_right(new_node);
-            node.update_size();
-            return Some(node.clone());
-        }
-        None
+        let path = self.path_mut(|_| Ordering::Greater);
+        self.unlink(path);
     }
 
     /// Removes the given key and its associated value from the tree.
     pub fn delete(&mut self, key: usize) {
-        self.root = BST::remove(&self.root, key);
+        // Look before walking with `path_mut` so that deleting a missing key
+        // doesn't copy nodes shared with a snapshot.
+        if !self.contains(key) {
+            return;
+        }
+
+        let path = self.path_mut(|node| key.cmp(&node.key));
+        self.unlink(path);
     }
 
-    fn remove(x: &Option<Node<V>>, key: usize) -> Option<Node<V>> {
-        if let Some(node) = x {
-            if key < node.get().key {
-                let new_node = BST::remove(&node.get().left, key);
-                node.set_left(new_node);
-            } else if key > node.get().key {
-                let new_node = BST::remove(&node.get().right, key);
-                node.set_right(new_node);
+    /// Splits the tree in two at the given key. Returns everything after the
+    /// given key, including the key, and leaves the rest in `self`.
+    ///
+    /// Only the nodes on the path to `key` are relinked, so this takes
+    /// O(height) time.
+    pub fn split_off(&mut self, key: usize) -> BST<V> {
+        let path = self.path_mut(|node| key.cmp(&node.key));
+
+        // Rebuild both halves bottom-up. Each node on the path goes to one
+        // side, taking its subtree on the far side along, and picks up the
+        // matching half of the split below it.
+        let mut left = None;
+        let mut right = None;
+        for node in path.into_iter().rev() {
+            let node_key = node.get().key;
+            if key < node_key {
+                node.set_left(right);
+                node.update_size();
+                right = Some(node);
+            } else if key > node_key {
+                node.set_right(left);
+                node.update_size();
+                left = Some(node);
             } else {
-                if node.get().right.is_none() {
-                    return node.get().left.as_ref().map(|n| n.clone());
-                }
-                if node.get().left.is_none() {
-                    return node.get().right.as_ref().map(|n| n.clone());
-                }
-
-                let temp = node.clone();
-                // minimum of the right replaces node to be deleted
-                let node = BST::minimum(&temp.get().right).unwrap();
+                left = node.get().left.clone();
+                node.set_left(None);
+                node.update_size();
+                right = Some(node);
+            }
+        }
 
-                // new node takes left and right of the deleted
-                let right = BST::remove_min(&temp.get().right);
-                node.set_right(right);
-                let left = temp.get().left.as_ref().map(|n| n.clone());
-                node.set_left(left);
+        self.root = left;
+        BST { root: right }
+    }
 
-                node.update_size();
-                return Some(node);
+    /// Moves all elements from `other` into `self`, leaving `other` empty.
+    ///
+    /// If all keys of one tree are smaller than all keys of the other, the
+    /// trees are joined under a new root in O(height) time. Otherwise the
+    /// elements of `other` are inserted one by one, and a value from `other`
+    /// replaces the value of the same key in `self`.
+    pub fn append(&mut self, other: &mut BST<V>) {
+        let other = std::mem::take(other);
+        let (self_min, self_max) = match (self.min(), self.max()) {
+            (Some(min), Some(max)) => (min, max),
+            _ => {
+                *self = other;
+                return;
             }
-            node.update_size();
-            return Some(node.clone());
+        };
+        let (other_min, other_max) = match (other.min(), other.max()) {
+            (Some(min), Some(max)) => (min, max),
+            _ => return,
+        };
+
+        if self_max < other_min {
+            let left = std::mem::take(self);
+            *self = BST::join(left, other);
+        } else if other_max < self_min {
+            let right = std::mem::take(self);
+            *self = BST::join(other, right);
+        } else {
+            self.extend(other);
         }
+    }
 
-        None
+    /// Joins two non-empty trees where every key in `left` is smaller than
+    /// every key in `right`. The largest node of `left` becomes the new root.
+    fn join(mut left: BST<V>, right: BST<V>) -> BST<V> {
+        let path = left.path_mut(|_| Ordering::Greater);
+        let max = path.last().unwrap().clone();
+        left.unlink(path);
+
+        max.set_left(left.root.take());
+        max.set_right(right.root.clone());
+        max.update_size();
+        BST { root: Some(max) }
     }
 
     /// Returns the smallest key in the tree.
@@ -241,15 +815,14 @@ impl<V: Clone> BST<V> {
     }
 
     fn minimum(x: &Option<Node<V>>) -> Option<Node<V>> {
-        if let Some(node) = x {
-            if node.get().left.is_none() {
-                return Some(node.clone());
-            } else {
-                return BST::minimum(&node.get().left);
+        let mut node = x.clone()?;
+        loop {
+            let left = node.get().left.clone();
+            match left {
+                Some(left) => node = left,
+                None => return Some(node),
             }
         }
-
-        None
     }
 
     /// Returns the largest key in the tree.
@@ -262,15 +835,14 @@ impl<V: Clone> BST<V> {
     }
 
     fn maximum(x: &Option<Node<V>>) -> Option<Node<V>> {
-        if let Some(node) = x {
-            if node.get().right.is_none() {
-                return Some(node.clone());
-            } else {
-                return BST::maximum(&node.get().right);
+        let mut node = x.clone()?;
+        loop {
+            let right = node.get().right.clone();
+            match right {
+                Some(right) => node = right,
+                None => return Some(node),
             }
         }
-
-        None
     }
 
     pub fn iter(&self) -> TreeIter<V> {
@@ -279,6 +851,19 @@ impl<V: Clone> BST<V> {
         iter
     }
 
+    /// Returns an iterator that starts at the entry with the given rank,
+    /// that is the entry with `rank` smaller keys in the tree, and goes on
+    /// in key order.
+    ///
+    /// Getting to the starting entry takes O(height) time, so reading page
+    /// `p` of `n` entries is `iter_from_rank(p * n).take(n)`. The iterator
+    /// is empty if `rank` is not smaller than the size of the tree.
+    pub fn iter_from_rank(&self, rank: usize) -> TreeIter<V> {
+        let mut iter = TreeIter { unvisited: Vec::new() };
+        iter.seek(&self.root, rank);
+        iter
+    }
+
     pub fn keys(&self) -> Vec<usize> {
         let mut v = Vec::new();
         BST::inorder(&self.root, &mut v);
@@ -286,10 +871,20 @@ impl<V: Clone> BST<V> {
     }
 
     fn inorder(x: &Option<Node<V>>, v: &mut Vec<usize>) {
-        if let Some(node) = x {
-            BST::inorder(&node.get().left, v);
-            v.push(node.get().key);
-            BST::inorder(&node.get().right, v);
+        let mut stack: Vec<Node<V>> = Vec::new();
+        let mut x = x.clone();
+        loop {
+            while let Some(node) = x {
+                x = node.get().left.clone();
+                stack.push(node);
+            }
+            match stack.pop() {
+                Some(node) => {
+                    v.push(node.get().key);
+                    x = node.get().right.clone();
+                }
+                None => return,
+            }
         }
     }
 
@@ -314,6 +909,8 @@ impl<V: Clone> BST<V> {
 }
 
 #[cfg(test)]
+// the original tests compare bools with `assert_eq!`
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
 
@@ -541,4 +1138,490 @@ mod tests {
         assert_eq!(tree_iter.next(), Some((2, "C".to_string())));
         assert_eq!(tree_iter.next(), Some((3, "E".to_string())));
     }
+
+    #[test]
+    fn iter_from_rank() {
+        let mut bst = BST::new();
+        populate_tree(&mut bst);
+        let entries: Vec<_> = bst.iter().collect();
+
+        for rank in 0..12 {
+            assert_eq!(bst.iter_from_rank(rank).collect::<Vec<_>>(),
+                entries.iter().skip(rank).cloned().collect::<Vec<_>>());
+        }
+        assert_eq!(BST::<String>::new().iter_from_rank(0).next(), None);
+
+        // pages of 2 entries
+        let page: Vec<_> = bst.iter_from_rank(2 * 2).take(2).map(|(key, _)| key).collect();
+        assert_eq!(page, vec![5, 6]);
+    }
+
+    #[test]
+    fn iterator_nth() {
+        let mut bst = BST::new();
+        populate_tree(&mut bst);
+        let entries: Vec<_> = bst.iter().collect();
+
+        // skip from every position by every distance
+        for start in 0..10 {
+            for n in 0..12 {
+                let mut iter = bst.iter();
+                let mut expected = entries.iter().cloned();
+                if start > 0 {
+                    assert_eq!(iter.nth(start - 1), expected.nth(start - 1));
+                }
+                assert_eq!(iter.nth(n), expected.nth(n));
+                assert_eq!(iter.collect::<Vec<_>>(), expected.collect::<Vec<_>>());
+            }
+        }
+
+        let bst = BST::from_sorted((0..100_000).map(|key| (key, key)));
+        let mut iter = bst.iter();
+        assert_eq!(iter.nth(50_000), Some((50_000, 50_000)));
+        assert_eq!(iter.nth(9_999), Some((60_000, 60_000)));
+        assert_eq!(iter.next(), Some((60_001, 60_001)));
+        assert_eq!(iter.nth(40_000), None);
+        assert_eq!(iter.next(), None);
+        assert_eq!(bst.iter_from_rank(99_999).collect::<Vec<_>>(), vec![(99_999, 99_999)]);
+    }
+
+    #[test]
+    fn snapshot() {
+        let mut bst = BST::new();
+        populate_tree(&mut bst);
+        let snapshot = bst.snapshot();
+
+        bst.put(5, "I".to_string());
+        bst.put(10, "Y".to_string());
+        bst.delete(3);
+        bst.delete_min();
+        bst.delete_max();
+
+        assert_eq!(bst.keys(), vec![2, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(bst.get(5), Some("I".to_string()));
+        assert_eq!(bst.size(), 7);
+
+        assert_eq!(snapshot.keys(), vec![1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(snapshot.get(5), Some("H".to_string()));
+        assert_eq!(snapshot.size(), 9);
+        assert_eq!(snapshot.level_order(), vec![8, 3, 9, 1, 7, 2, 5, 4, 6]);
+
+        // writes to the snapshot don't affect the tree either
+        let mut snapshot = snapshot;
+        snapshot.put(4, "F".to_string());
+        assert_eq!(snapshot.get(4), Some("F".to_string()));
+        assert_eq!(bst.get(4), Some("G".to_string()));
+    }
+
+    #[test]
+    fn snapshot_shares_untouched_nodes() {
+        let mut bst = BST::new();
+        populate_tree(&mut bst);
+        let snapshot = bst.snapshot();
+
+        //          8(S)
+        //         /    \
+        //       3(E)   9(X)  <- only the path to 9 is copied
+        bst.put(9, "Z".to_string());
+
+        let root = bst.root.as_ref().unwrap();
+        let old_root = snapshot.root.as_ref().unwrap();
+        assert!(!Rc::ptr_eq(&root.0, &old_root.0));
+
+        let left = root.get().left.clone().unwrap();
+        let old_left = old_root.get().left.clone().unwrap();
+        assert!(Rc::ptr_eq(&left.0, &old_left.0));
+
+        let right = root.get().right.clone().unwrap();
+        let old_right = old_root.get().right.clone().unwrap();
+        assert!(!Rc::ptr_eq(&right.0, &old_right.0));
+    }
+
+    #[test]
+    fn no_copy_without_snapshot() {
+        let mut bst = BST::new();
+        populate_tree(&mut bst);
+        let ptr = Rc::as_ptr(&bst.root.as_ref().unwrap().0);
+        bst.put(10, "Y".to_string());
+        bst.delete(7);
+        assert_eq!(Rc::as_ptr(&bst.root.as_ref().unwrap().0), ptr);
+    }
+
+    // Builds the degenerate tree that inserting `0..n` in order produces,
+    // a chain of right children, directly from the bottom up. Inserting the
+    // keys with `put` takes O(n^2) time on this shape.
+    fn sorted_chain(n: usize) -> BST<usize> {
+        let mut root = None;
+        for key in (0..n).rev() {
+            let node = Node::new(key, key);
+            node.set_right(root);
+            node.update_size();
+            root = Some(node);
+        }
+        BST { root }
+    }
+
+    #[test]
+    fn sorted_puts_on_small_stack() {
+        // Recursing once per level of the tree would need far more stack
+        // than this for a tree of height 10000.
+        let child = std::thread::Builder::new()
+            .stack_size(32 * 1024)
+            .spawn(|| {
+                let n = 10_000;
+                let mut bst = BST::new();
+                for key in 0..n {
+                    bst.put(key, key);
+                }
+                assert_eq!(bst.size(), n);
+                assert_eq!(bst.get(n - 1), Some(n - 1));
+                assert_eq!(bst.min(), Some(0));
+                assert_eq!(bst.max(), Some(n - 1));
+
+                bst.delete(0);
+                bst.delete_min();
+                bst.delete_max();
+                assert_eq!(bst.keys().len(), n - 3);
+                assert_eq!(bst.iter().count(), n - 3);
+            })
+            .unwrap();
+        child.join().unwrap();
+    }
+
+    #[test]
+    fn million_node_chain() {
+        // Every `put` of an increasing key walks the whole chain below it,
+        // so putting a million keys one by one would take O(n^2) time, about
+        // 5 * 10^11 steps. The chain they would build is made directly
+        // instead, and `sorted_puts_on_small_stack` covers `put` itself.
+        let n = 1_000_000;
+        let mut bst = sorted_chain(n);
+        assert_eq!(bst.size(), n);
+
+        // each of these walks the whole chain
+        bst.put(n, n);
+        assert_eq!(bst.size(), n + 1);
+        assert_eq!(bst.get(n), Some(n));
+        assert!(bst.contains(n - 1));
+        assert_eq!(bst.max(), Some(n));
+        bst.delete_max();
+        bst.delete(n - 1);
+        assert_eq!(bst.max(), Some(n - 2));
+
+        // the other end, and deleting from the middle of the chain
+        assert_eq!(bst.min(), Some(0));
+        bst.delete_min();
+        assert_eq!(bst.min(), Some(1));
+        bst.delete(n / 2);
+        assert_eq!(bst.size(), n - 3);
+
+        assert_eq!(bst.keys().len(), n - 3);
+        assert_eq!(bst.iter().map(|(key, _)| key).last(), Some(n - 2));
+        assert_eq!(bst.level_order().len(), n - 3);
+
+        // a snapshot keeps the chain alive until both are dropped
+        let snapshot = bst.snapshot();
+        bst.delete(n - 2);
+        drop(bst);
+        assert_eq!(snapshot.size(), n - 3);
+        drop(snapshot);
+    }
+
+    #[test]
+    fn collect_and_extend() {
+        let mut bst: BST<char> = vec![(3, 'c'), (1, 'a'), (2, 'b')].into_iter().collect();
+        assert_eq!(bst.keys(), vec![1, 2, 3]);
+
+        bst.extend(vec![(4, 'd'), (1, 'z')]);
+        assert_eq!(bst.size(), 4);
+        assert_eq!(bst.get(1), Some('z'));
+
+        let from_array = BST::from([(1, 'z'), (2, 'b'), (3, 'c'), (4, 'd')]);
+        assert_eq!(bst, from_array);
+    }
+
+    #[test]
+    fn into_iterator() {
+        let mut bst = BST::new();
+        populate_tree(&mut bst);
+
+        let mut keys = Vec::new();
+        for (key, _) in &bst {
+            keys.push(key);
+        }
+        assert_eq!(keys, bst.keys());
+
+        let snapshot = bst.snapshot();
+        let entries: Vec<(usize, String)> = bst.into_iter().collect();
+        assert_eq!(entries.len(), 9);
+        assert_eq!(entries[0], (1, "A".to_string()));
+        assert_eq!(entries[8], (9, "X".to_string()));
+
+        // the snapshot shared every node, so it is left intact
+        assert_eq!(snapshot.iter().collect::<Vec<_>>(), entries);
+
+        // dropping a partly consumed iterator frees the rest
+        let mut iter = snapshot.into_iter();
+        assert_eq!(iter.next(), Some((1, "A".to_string())));
+    }
+
+    #[test]
+    fn into_iter_moves_values() {
+        let mut bst = BST::new();
+        let values: Vec<Rc<usize>> = (0..5).map(Rc::new).collect();
+        for (key, value) in values.iter().enumerate() {
+            bst.put(key, Rc::clone(value));
+        }
+
+        let moved: Vec<Rc<usize>> = bst.into_iter().map(|(_, value)| value).collect();
+        // no clones of the values are left behind in the tree
+        assert!(values.iter().all(|value| Rc::strong_count(value) == 2));
+        drop(moved);
+        assert!(values.iter().all(|value| Rc::strong_count(value) == 1));
+    }
+
+    #[test]
+    fn index() {
+        let mut bst = BST::new();
+        populate_tree(&mut bst);
+
+        assert_eq!(bst[5], "H");
+        assert_eq!(bst[&8], "S");
+    }
+
+    #[test]
+    fn index_snapshot_while_consuming_tree() {
+        let mut bst = BST::new();
+        populate_tree(&mut bst);
+        let snapshot = bst.snapshot();
+
+        // the nodes the tree shares with the snapshot are only read
+        let value = &snapshot[5];
+        bst.rebalance();
+        assert_eq!(value, "H");
+        let entries: Vec<(usize, String)> = bst.into_iter().collect();
+        assert_eq!(value, "H");
+        assert_eq!(snapshot.iter().collect::<Vec<_>>(), entries);
+    }
+
+    #[test]
+    #[should_panic(expected = "no entry found for key")]
+    fn index_missing_key() {
+        let bst: BST<String> = BST::new();
+        let _ = &bst[1];
+    }
+
+    #[test]
+    fn deep_clone() {
+        let mut bst = BST::new();
+        populate_tree(&mut bst);
+
+        let copy = bst.clone();
+        assert_eq!(copy, bst);
+        assert_eq!(copy.level_order(), bst.level_order());
+        assert_eq!(copy.size(), 9);
+
+        let root = bst.root.as_ref().unwrap();
+        let copy_root = copy.root.as_ref().unwrap();
+        assert!(!Rc::ptr_eq(&root.0, &copy_root.0));
+        assert_eq!(Rc::strong_count(&root.0), 1);
+
+        bst.put(5, "I".to_string());
+        assert_ne!(copy, bst);
+        assert_eq!(copy.get(5), Some("H".to_string()));
+    }
+
+    #[test]
+    fn compare_and_debug() {
+        let a: BST<i32> = vec![(1, 10), (2, 20)].into_iter().collect();
+        let b: BST<i32> = vec![(2, 20), (1, 10)].into_iter().collect();
+        let c: BST<i32> = vec![(1, 10), (2, 21)].into_iter().collect();
+
+        // same entries, different shapes
+        assert_ne!(a.level_order(), b.level_order());
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(format!("{:?}", a), "{1: 10, 2: 20}");
+        assert_eq!(format!("{:?}", BST::<i32>::default()), "{}");
+    }
+
+    #[test]
+    fn from_sorted() {
+        //         4
+        //       /   \
+        //     2       6
+        //    / \     / \
+        //   1   3   5   7
+        let bst = BST::from_sorted((1..8).map(|key| (key, key * 10)));
+        assert_eq!(bst.level_order(), vec![4, 2, 6, 1, 3, 5, 7]);
+        assert_eq!(bst.get(5), Some(50));
+        assert_eq!(bst.size(), 7);
+        assert_eq!(bst.check_invariants(), Ok(()));
+
+        let empty: BST<usize> = BST::from_sorted(vec![]);
+        assert!(empty.is_empty());
+
+        // the last value of a repeated key wins
+        let bst = BST::from_sorted(vec![(1, 'a'), (2, 'b'), (2, 'c'), (3, 'd')]);
+        assert_eq!(bst.keys(), vec![1, 2, 3]);
+        assert_eq!(bst.get(2), Some('c'));
+        assert_eq!(bst.size(), 3);
+        assert_eq!(bst.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn from_sorted_is_balanced() {
+        for n in 0..70 {
+            let bst = BST::from_sorted((0..n).map(|key| (key, ())));
+            assert_eq!(bst.keys(), (0..n).collect::<Vec<_>>());
+            // the height of a complete tree of n nodes
+            let expected = (usize::BITS - n.leading_zeros()) as usize;
+            assert_eq!(bst.height(), expected);
+            assert_eq!(bst.check_invariants(), Ok(()));
+        }
+
+        let n = 1_000_000;
+        let bst = BST::from_sorted((0..n).map(|key| (key, key)));
+        assert_eq!(bst.size(), n);
+        assert_eq!(bst.height(), 20);
+        assert_eq!(bst.get(123_456), Some(123_456));
+    }
+
+    #[test]
+    #[should_panic(expected = "keys must be in ascending order")]
+    fn from_unsorted() {
+        BST::from_sorted(vec![(1, 'a'), (3, 'c'), (2, 'b')]);
+    }
+
+    #[test]
+    fn rebalance() {
+        let n = 10_000;
+        let mut bst = sorted_chain(n);
+        let snapshot = bst.snapshot();
+        assert_eq!(bst.height(), n);
+
+        bst.rebalance();
+        assert_eq!(bst.height(), 14);
+        assert_eq!(bst.size(), n);
+        assert_eq!(bst.keys(), (0..n).collect::<Vec<_>>());
+        assert_eq!(bst.check_invariants(), Ok(()));
+
+        // still a plain BST afterwards
+        bst.put(n, n);
+        bst.delete(0);
+        assert_eq!(bst.min(), Some(1));
+        assert_eq!(bst.max(), Some(n));
+
+        assert_eq!(snapshot.height(), n);
+        assert_eq!(snapshot.size(), n);
+
+        let mut empty: BST<usize> = BST::new();
+        empty.rebalance();
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn split_off() {
+        for key in 0..11 {
+            let mut bst = BST::new();
+            populate_tree(&mut bst);
+
+            let right = bst.split_off(key);
+            let expected: Vec<usize> = (1..10).filter(|k| *k < key).collect();
+            assert_eq!(bst.keys(), expected);
+            assert_eq!(bst.size(), expected.len());
+            let expected: Vec<usize> = (1..10).filter(|k| *k >= key).collect();
+            assert_eq!(right.keys(), expected);
+            assert_eq!(right.size(), expected.len());
+            assert_eq!(bst.check_invariants(), Ok(()));
+            assert_eq!(right.check_invariants(), Ok(()));
+        }
+
+        let mut empty: BST<usize> = BST::new();
+        assert!(empty.split_off(1).is_empty());
+    }
+
+    #[test]
+    fn split_off_relinks_path() {
+        let mut bst = BST::new();
+        populate_tree(&mut bst);
+        let snapshot = bst.snapshot();
+
+        //          8(S)
+        //         /    \
+        //       3(E)   9(X)
+        //      /   \
+        //  1(A)     7(R)        split at 6: only 8, 3, 7, 5 and 6 are
+        //     \     /           on the path, 1-2 and 9 stay as they are
+        //    2(C) 5(H)
+        //        /   \
+        //      4(G)  6(M)
+        let right = bst.split_off(6);
+        assert_eq!(bst.level_order(), vec![3, 1, 5, 2, 4]);
+        assert_eq!(right.level_order(), vec![8, 7, 9, 6]);
+
+        // the subtree under 1 is shared with the snapshot, not copied
+        let left_of = |node: &Node<String>| node.get().left.clone().unwrap();
+        let old_1 = left_of(&left_of(snapshot.root.as_ref().unwrap()));
+        let new_1 = left_of(bst.root.as_ref().unwrap());
+        assert!(Rc::ptr_eq(&old_1.0, &new_1.0));
+
+        assert_eq!(snapshot.keys(), vec![1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(snapshot.level_order(), vec![8, 3, 9, 1, 7, 2, 5, 4, 6]);
+    }
+
+    #[test]
+    fn append() {
+        let mut left = BST::from_sorted((0..5).map(|key| (key, key)));
+        let mut right = BST::from_sorted((5..12).map(|key| (key, key)));
+        let right_root = right.root.clone().unwrap();
+
+        left.append(&mut right);
+        assert!(right.is_empty());
+        assert_eq!(left.keys(), (0..12).collect::<Vec<_>>());
+        assert_eq!(left.size(), 12);
+        assert_eq!(left.check_invariants(), Ok(()));
+        // joined, not reinserted: the root of right is now a child
+        let new_right = left.root.as_ref().unwrap().get().right.clone().unwrap();
+        assert!(Rc::ptr_eq(&new_right.0, &right_root.0));
+        drop(right_root);
+
+        // joining in the other order
+        let mut low = BST::from_sorted((0..3).map(|key| (key, 0)));
+        let mut high = BST::from_sorted((10..13).map(|key| (key, 1)));
+        high.append(&mut low);
+        assert_eq!(high.keys(), vec![0, 1, 2, 10, 11, 12]);
+        assert_eq!(high.check_invariants(), Ok(()));
+
+        // overlapping keys take the values of other
+        let mut a = BST::from_sorted(vec![(1, 'a'), (3, 'c'), (5, 'e')]);
+        let mut b = BST::from_sorted(vec![(2, 'B'), (3, 'C')]);
+        a.append(&mut b);
+        assert_eq!(a.iter().collect::<Vec<_>>(),
+            vec![(1, 'a'), (2, 'B'), (3, 'C'), (5, 'e')]);
+        assert_eq!(a.check_invariants(), Ok(()));
+
+        // empty trees on either side
+        let mut empty = BST::new();
+        let mut c = BST::from_sorted(vec![(1, 'a')]);
+        empty.append(&mut c);
+        assert_eq!(empty.size(), 1);
+        empty.append(&mut c);
+        assert_eq!(empty.size(), 1);
+    }
+
+    #[test]
+    fn split_then_append() {
+        let n = 1000;
+        let mut bst: BST<usize> = BST::from_sorted((0..n).map(|key| (key, key)));
+        let snapshot = bst.snapshot();
+        for key in (0..n).step_by(37) {
+            let mut right = bst.split_off(key);
+            right.put(n + key, key);
+            bst.append(&mut right);
+            assert_eq!(bst.check_invariants(), Ok(()));
+        }
+        assert_eq!(bst.size(), n + (0..n).step_by(37).count());
+        assert_eq!(snapshot.keys(), (0..n).collect::<Vec<_>>());
+    }
 }
@@ -0,0 +1,296 @@
+use std::cmp::Ordering;
+
+use crate::{InvariantViolation, Node, TreeIter, BST};
+
+/// A Binary Search Tree that keeps every value put under a key.
+///
+/// A key that is already in the tree is inserted again to the right of its
+/// existing occurrences instead of replacing their value. Every key is
+/// smaller than the keys in its right subtree or equal to them, and the
+/// values of a key stay in the order they were put in.
+///
+/// The first occurrence of a key is the highest one on the search path, so
+/// looking it up takes O(height) time like in a `BST`.
+#[derive(Debug)]
+pub struct MultiBST<V: Clone> {
+    tree: BST<V>,
+}
+
+impl<V: Clone> Default for MultiBST<V> {
+    fn default() -> Self {
+        MultiBST::new()
+    }
+}
+
+impl<V: Clone> MultiBST<V> {
+    /// Creates a new empty tree.
+    pub fn new() -> Self {
+        MultiBST { tree: BST::new() }
+    }
+
+    /// Returns a snapshot of the tree in O(1) time, as in `BST::snapshot`.
+    pub fn snapshot(&self) -> Self {
+        MultiBST { tree: self.tree.snapshot() }
+    }
+
+    /// Returns `true` if the tree has no node elements.
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Returns the number of elements in the tree, counting every occurrence
+    /// of a key.
+    pub fn size(&self) -> usize {
+        self.tree.size()
+    }
+
+    pub fn contains(&self, key: usize) -> bool {
+        self.tree.contains(key)
+    }
+
+    /// Returns a clone of the first value put under the given key.
+    pub fn get(&self, key: usize) -> Option<V> {
+        self.tree.get(key)
+    }
+
+    /// Returns clones of all values put under the given key, in the order
+    /// they were put in.
+    pub fn get_all(&self, key: usize) -> Vec<V> {
+        let count = self.count(key);
+        self.tree.iter_from_rank(self.rank(key))
+            .take(count)
+            .map(|(_, value)| value)
+            .collect()
+    }
+
+    /// Returns the number of values under the given key.
+    ///
+    /// This takes O(height) time however many there are, by counting the
+    /// keys smaller than `key` and the keys not larger than `key` from the
+    /// subtree sizes.
+    pub fn count(&self, key: usize) -> usize {
+        self.count_before(|node_key| key < node_key) - self.rank(key)
+    }
+
+    /// Returns the number of keys smaller than `key`.
+    fn rank(&self, key: usize) -> usize {
+        self.count_before(|node_key| key <= node_key)
+    }
+
+    /// Walks down from the root, going left at the nodes for which
+    /// `goes_left` returns `true`, and returns the number of nodes left
+    /// behind on the left.
+    fn count_before<F: Fn(usize) -> bool>(&self, goes_left: F) -> usize {
+        let mut count = 0;
+        let mut x = self.tree.root.clone();
+        while let Some(node) = x {
+            if goes_left(node.get().key) {
+                x = node.get().left.clone();
+            } else {
+                count += Node::size(&node.get().left) + 1;
+                x = node.get().right.clone();
+            }
+        }
+        count
+    }
+
+    /// Inserts the given key-value pair into the tree, after the values
+    /// already under the same key.
+    pub fn put(&mut self, key: usize, value: V) {
+        let path = self.tree.path_mut(|node| {
+            if key < node.key {
+                Ordering::Less
+            } else {
+                Ordering::Greater
+            }
+        });
+        let node = Some(Node::new(key, value));
+        match path.last() {
+            Some(parent) if key < parent.get().key => parent.set_left(node),
+            Some(parent) => parent.set_right(node),
+            None => self.tree.root = node,
+        }
+
+        for node in path.iter().rev() {
+            node.update_size();
+        }
+    }
+
+    /// Removes the first value under the given key from the tree.
+    pub fn delete(&mut self, key: usize) {
+        // `BST::delete` stops at the first node with the key, which is the
+        // first occurrence, and the successor that replaces it keeps every
+        // key in its left subtree smaller than itself.
+        self.tree.delete(key);
+    }
+
+    /// Removes all values under the given key from the tree.
+    pub fn delete_all(&mut self, key: usize) {
+        while self.tree.contains(key) {
+            self.tree.delete(key);
+        }
+    }
+
+    /// Removes the first value under the smallest key from the tree.
+    pub fn delete_min(&mut self) {
+        self.tree.delete_min();
+    }
+
+    /// Removes the last value under the largest key from the tree.
+    pub fn delete_max(&mut self) {
+        self.tree.delete_max();
+    }
+
+    /// Returns the smallest key in the tree.
+    pub fn min(&self) -> Option<usize> {
+        self.tree.min()
+    }
+
+    /// Returns the largest key in the tree.
+    pub fn max(&self) -> Option<usize> {
+        self.tree.max()
+    }
+
+    /// Returns an iterator over all entries in key order, with the values
+    /// of the same key in the order they were put in.
+    pub fn iter(&self) -> TreeIter<V> {
+        self.tree.iter()
+    }
+
+    /// Returns all keys in order, a key appearing once for each of its
+    /// values.
+    pub fn keys(&self) -> Vec<usize> {
+        self.tree.keys()
+    }
+
+    /// Checks the ordering and `size` invariants of the tree, as in
+    /// `BST::check_invariants` but allowing a key to be equal to the keys
+    /// in its right subtree.
+    pub fn check_invariants(&self) -> Result<(), InvariantViolation> {
+        self.tree.check_with(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+
+    fn populate_tree() -> MultiBST<&'static str> {
+        let mut tree = MultiBST::new();
+        for (key, value) in [(5, "a"), (3, "b"), (5, "c"), (8, "d"), (5, "e"), (3, "f"), (1, "g")] {
+            tree.put(key, value);
+        }
+        tree
+    }
+
+    #[test]
+    fn put_and_get() {
+        let mut tree = populate_tree();
+
+        assert_eq!(tree.size(), 7);
+        assert_eq!(tree.keys(), vec![1, 3, 3, 5, 5, 5, 8]);
+        assert_eq!(tree.get(5), Some("a"));
+        assert_eq!(tree.get_all(5), vec!["a", "c", "e"]);
+        assert_eq!(tree.get_all(3), vec!["b", "f"]);
+        assert_eq!(tree.get_all(8), vec!["d"]);
+        assert_eq!(tree.get_all(4), Vec::<&str>::new());
+        assert_eq!(tree.check_invariants(), Ok(()));
+
+        tree.put(5, "h");
+        assert_eq!(tree.get_all(5), vec!["a", "c", "e", "h"]);
+        assert_eq!(tree.iter().map(|(_, value)| value).collect::<Vec<_>>(),
+            vec!["g", "b", "f", "a", "c", "e", "h", "d"]);
+    }
+
+    #[test]
+    fn count() {
+        let tree = populate_tree();
+
+        assert_eq!(tree.count(5), 3);
+        assert_eq!(tree.count(3), 2);
+        assert_eq!(tree.count(1), 1);
+        assert_eq!(tree.count(0), 0);
+        assert_eq!(tree.count(9), 0);
+        assert_eq!(MultiBST::<()>::new().count(1), 0);
+
+        let mut tree = MultiBST::new();
+        tree.put(usize::MAX, ());
+        tree.put(usize::MAX, ());
+        assert_eq!(tree.count(usize::MAX), 2);
+    }
+
+    #[test]
+    fn delete() {
+        let mut tree = populate_tree();
+        let snapshot = tree.snapshot();
+
+        tree.delete(5);
+        assert_eq!(tree.get_all(5), vec!["c", "e"]);
+        tree.delete(3);
+        assert_eq!(tree.get_all(3), vec!["f"]);
+        tree.delete(4);
+        assert_eq!(tree.size(), 5);
+        assert_eq!(tree.check_invariants(), Ok(()));
+
+        tree.delete_all(5);
+        assert!(!tree.contains(5));
+        assert_eq!(tree.keys(), vec![1, 3, 8]);
+        tree.delete_all(5);
+        assert_eq!(tree.size(), 3);
+        assert_eq!(tree.check_invariants(), Ok(()));
+
+        assert_eq!(snapshot.get_all(5), vec!["a", "c", "e"]);
+        assert_eq!(snapshot.size(), 7);
+    }
+
+    #[test]
+    fn random_operations() {
+        // the model holds (key, value) pairs in key order, and in insertion
+        // order for equal keys
+        type State = (MultiBST<usize>, Vec<(usize, usize)>);
+        const KEYS: usize = 16;
+
+        test_util::random_operations(
+            20,
+            300,
+            |_| (MultiBST::new(), Vec::new()),
+            &[
+                (4, |(tree, model): &mut State, rng| {
+                    let (key, value) = (rng.below(KEYS), rng.below(1000));
+                    tree.put(key, value);
+                    let i = model.partition_point(|&(k, _)| k <= key);
+                    model.insert(i, (key, value));
+                }),
+                (2, |(tree, model), rng| {
+                    let key = rng.below(KEYS);
+                    tree.delete(key);
+                    if let Some(i) = model.iter().position(|&(k, _)| k == key) {
+                        model.remove(i);
+                    }
+                }),
+                (1, |(tree, model), rng| {
+                    let key = rng.below(KEYS);
+                    tree.delete_all(key);
+                    model.retain(|&(k, _)| k != key);
+                }),
+                (1, |(tree, model), _| {
+                    tree.delete_min();
+                    if !model.is_empty() {
+                        model.remove(0);
+                    }
+                }),
+            ],
+            |(tree, model), rng| {
+                let key = rng.below(KEYS);
+                assert_eq!(tree.check_invariants(), Ok(()));
+                assert_eq!(tree.count(key), model.iter().filter(|&&(k, _)| k == key).count());
+                assert_eq!(tree.get_all(key), model.iter()
+                    .filter(|&&(k, _)| k == key)
+                    .map(|&(_, v)| v)
+                    .collect::<Vec<_>>());
+            },
+            |(tree, model)| assert_eq!(tree.iter().collect::<Vec<_>>(), model),
+        );
+    }
+}
@@ -0,0 +1,424 @@
+use std::rc::Rc;
+
+use crate::rng::Rng;
+use crate::{InvariantViolation, Node, TreeIter, BST};
+
+// The smaller nodes, the node with the key and the larger nodes of a split
+type Split<V> = (Option<Node<V>>, Option<Node<V>>, Option<Node<V>>);
+
+/// A randomized Binary Search Tree.
+///
+/// Every insertion puts the new key at the root of the subtree it lands in
+/// with probability `1 / (n + 1)`, where `n` is the `size` of that subtree,
+/// and deletions join the two children of the removed node at random. The
+/// resulting tree has the shape of a BST built from a random permutation of
+/// its keys no matter what order they were inserted in, so its expected
+/// height is O(log n) even for sorted input.
+///
+/// The random choices come from a seedable generator, so a tree created with
+/// `with_seed` always ends up in the same shape for the same operations.
+#[derive(Debug)]
+pub struct RandomizedBST<V: Clone> {
+    pub(crate) tree: BST<V>,
+    rng: Rng,
+}
+
+impl<V: Clone> Default for RandomizedBST<V> {
+    fn default() -> Self {
+        RandomizedBST::new()
+    }
+}
+
+impl<V: Clone> RandomizedBST<V> {
+    /// Creates a new empty tree with a randomly seeded generator.
+    pub fn new() -> Self {
+        RandomizedBST {
+            tree: BST::new(),
+            rng: Rng::from_entropy(),
+        }
+    }
+
+    /// Creates a new empty tree whose random choices are determined by `seed`.
+    pub fn with_seed(seed: u64) -> Self {
+        RandomizedBST {
+            tree: BST::new(),
+            rng: Rng::with_seed(seed),
+        }
+    }
+
+    /// Returns `true` if the tree has no node elements.
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Returns the number of elements in the tree.
+    pub fn size(&self) -> usize {
+        self.tree.size()
+    }
+
+    pub fn contains(&self, key: usize) -> bool {
+        self.tree.contains(key)
+    }
+
+    /// Returns a clone of the value associated with the given key.
+    pub fn get(&self, key: usize) -> Option<V> {
+        self.tree.get(key)
+    }
+
+    /// Inserts the given key-value pair into the tree. If the tree already
+    /// contains the given key, the associated value is updated.
+    pub fn put(&mut self, key: usize, value: V) {
+        // Taking the root leaves it referred to only by `root` when there is
+        // no snapshot, so `unshared` doesn't copy it.
+        let root = self.tree.root.take();
+        self.tree.root = Some(self.insert(&root, key, value));
+    }
+
+    fn insert(&mut self, x: &Option<Node<V>>, key: usize, value: V) -> Node<V> {
+        let node = match x {
+            Some(node) => node,
+            None => return Node::new(key, value),
+        };
+
+        if key != node.get().key && self.rng.below(Node::size(x) + 1) == 0 {
+            return RandomizedBST::insert_at_root(x, key, value);
+        }
+
+        let node = node.unshared();
+        let node_key = node.get().key;
+        if key < node_key {
+            let new_node = self.insert(&node.get().left, key, value);
+            node.set_left(Some(new_node));
+        } else if key > node_key {
+            let new_node = self.insert(&node.get().right, key, value);
+            node.set_right(Some(new_node));
+        } else {
+            // The shape doesn't change, only the value.
+            node.set_value(value);
+            return node;
+        }
+        node.update_size();
+        node
+    }
+
+    fn insert_at_root(x: &Option<Node<V>>, key: usize, value: V) -> Node<V> {
+        let (left, node, right) = RandomizedBST::split(x, key);
+        let node = match node {
+            Some(node) => {
+                node.set_value(value);
+                node
+            }
+            None => Node::new(key, value),
+        };
+        node.set_left(left);
+        node.set_right(right);
+        node.update_size();
+        node
+    }
+
+    /// Splits the subtree into the nodes with keys smaller than `key`, the
+    /// node with `key` if there is one, detached from its children, and the
+    /// nodes with keys larger than `key`.
+    fn split(x: &Option<Node<V>>, key: usize) -> Split<V> {
+        let node = match x {
+            Some(node) => node.unshared(),
+            None => return (None, None, None),
+        };
+
+        let node_key = node.get().key;
+        if key < node_key {
+            let (left, found, right) = RandomizedBST::split(&node.get().left, key);
+            node.set_left(right);
+            node.update_size();
+            (left, found, Some(node))
+        } else if key > node_key {
+            let (left, found, right) = RandomizedBST::split(&node.get().right, key);
+            node.set_right(left);
+            node.update_size();
+            (Some(node), found, right)
+        } else {
+            let left = node.get().left.clone();
+            let right = node.get().right.clone();
+            node.set_left(None);
+            node.set_right(None);
+            (left, Some(node), right)
+        }
+    }
+
+    /// Removes the smallest key and its associated value from the tree.
+    pub fn delete_min(&mut self) {
+        // The minimum has no left child, so joining its children is just
+        // taking the right one. No random choice is needed.
+        self.tree.delete_min();
+    }
+
+    /// Removes the largest key and its associated value from the tree.
+    pub fn delete_max(&mut self) {
+        self.tree.delete_max();
+    }
+
+    /// Removes the given key and its associated value from the tree.
+    pub fn delete(&mut self, key: usize) {
+        let root = self.tree.root.take();
+        let shared = root.as_ref().is_some_and(|node| Rc::strong_count(&node.0) > 1);
+        self.tree.root = match self.remove(&root, key, shared) {
+            Some(new_root) => new_root,
+            None => root,
+        };
+    }
+
+    /// Removes `key` from the subtree and returns the new subtree, or `None`
+    /// if the key isn't there. Nodes are only made safe to modify on the way
+    /// back up, so deleting a missing key doesn't copy nodes shared with a
+    /// snapshot. `shared` tells whether a node on the way down was shared.
+    fn remove(&mut self, x: &Option<Node<V>>, key: usize, shared: bool) -> Option<Option<Node<V>>> {
+        let node = x.as_ref()?;
+        let shared = shared || Rc::strong_count(&node.0) > 1;
+
+        let node_key = node.get().key;
+        if key == node_key {
+            // Children of a node owned alone are detached, so that joining
+            // them doesn't see them as shared and copy them.
+            let (left, right) = if shared {
+                (node.get().left.clone(), node.get().right.clone())
+            } else {
+                let left = node.get().left.clone();
+                let right = node.get().right.clone();
+                node.set_left(None);
+                node.set_right(None);
+                (left, right)
+            };
+            return Some(self.join(&left, &right));
+        }
+
+        let new_node = if key < node_key {
+            self.remove(&node.get().left, key, shared)?
+        } else {
+            self.remove(&node.get().right, key, shared)?
+        };
+        let node = node.unshared();
+        if key < node_key {
+            node.set_left(new_node);
+        } else {
+            node.set_right(new_node);
+        }
+        node.update_size();
+        Some(Some(node))
+    }
+
+    /// Joins two subtrees where every key in `a` is smaller than every key
+    /// in `b`. The root of the result is taken from `a` or `b` with
+    /// probability proportional to their sizes.
+    fn join(&mut self, a: &Option<Node<V>>, b: &Option<Node<V>>) -> Option<Node<V>> {
+        let m = Node::size(a);
+        let n = Node::size(b);
+        if m == 0 {
+            return b.clone();
+        }
+        if n == 0 {
+            return a.clone();
+        }
+
+        if self.rng.below(m + n) < m {
+            let node = a.as_ref().unwrap().unshared();
+            let new_node = self.join(&node.get().right, b);
+            node.set_right(new_node);
+            node.update_size();
+            Some(node)
+        } else {
+            let node = b.as_ref().unwrap().unshared();
+            let new_node = self.join(a, &node.get().left);
+            node.set_left(new_node);
+            node.update_size();
+            Some(node)
+        }
+    }
+
+    /// Returns the smallest key in the tree.
+    pub fn min(&self) -> Option<usize> {
+        self.tree.min()
+    }
+
+    /// Returns the largest key in the tree.
+    pub fn max(&self) -> Option<usize> {
+        self.tree.max()
+    }
+
+    pub fn iter(&self) -> TreeIter<V> {
+        self.tree.iter()
+    }
+
+    /// Returns an iterator that starts at the entry with the given rank, as
+    /// in `BST::iter_from_rank`.
+    pub fn iter_from_rank(&self, rank: usize) -> TreeIter<V> {
+        self.tree.iter_from_rank(rank)
+    }
+
+    pub fn keys(&self) -> Vec<usize> {
+        self.tree.keys()
+    }
+
+    pub fn level_order(&self) -> Vec<usize> {
+        self.tree.level_order()
+    }
+
+    /// Checks the ordering and `size` invariants of the tree, as in
+    /// `BST::check_invariants`.
+    pub fn check_invariants(&self) -> Result<(), InvariantViolation> {
+        self.tree.check_invariants()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RawNode;
+    use std::cell::RefCell;
+
+    #[test]
+    fn put_and_get() {
+        let mut tree = RandomizedBST::with_seed(42);
+        for key in &[8, 3, 1, 7, 2, 5, 9, 6, 4] {
+            tree.put(*key, key * 10);
+        }
+
+        assert_eq!(tree.size(), 9);
+        assert_eq!(tree.keys(), vec![1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(tree.get(5), Some(50));
+        assert_eq!(tree.get(10), None);
+
+        tree.put(5, 55);
+        assert_eq!(tree.get(5), Some(55));
+        assert_eq!(tree.size(), 9);
+        assert_eq!(tree.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn same_seed_same_shape() {
+        let mut a = RandomizedBST::with_seed(7);
+        let mut b = RandomizedBST::with_seed(7);
+        for key in 0..100 {
+            a.put(key, ());
+            b.put(key, ());
+        }
+        for key in (0..100).step_by(3) {
+            a.delete(key);
+            b.delete(key);
+        }
+
+        assert_eq!(a.level_order(), b.level_order());
+
+        let mut c = RandomizedBST::with_seed(8);
+        for key in 0..100 {
+            c.put(key, ());
+        }
+        assert_ne!(a.level_order(), c.level_order());
+    }
+
+    #[test]
+    fn sorted_input_stays_balanced() {
+        let mut tree = RandomizedBST::with_seed(1);
+        let n = 10_000;
+        for key in 0..n {
+            tree.put(key, key);
+        }
+
+        assert_eq!(tree.size(), n);
+        assert_eq!(tree.min(), Some(0));
+        assert_eq!(tree.max(), Some(n - 1));
+        // the expected height is about 3 * log2(n), far from n
+        assert!(tree.tree.height() < 60);
+        assert_eq!(tree.check_invariants(), Ok(()));
+
+        for key in (0..n).filter(|k| k % 2 == 0) {
+            tree.delete(key);
+        }
+        assert_eq!(tree.size(), n / 2);
+        assert!(tree.tree.height() < 60);
+        assert!(tree.iter().all(|(key, value)| key % 2 == 1 && key == value));
+        assert_eq!(tree.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn delete() {
+        let mut tree = RandomizedBST::with_seed(3);
+
+        // delete any key of an empty tree
+        tree.delete(8);
+        tree.delete_min();
+        tree.delete_max();
+
+        for key in 1..10 {
+            tree.put(key, key);
+        }
+
+        tree.delete(3);
+        tree.delete(10);
+        assert_eq!(tree.keys(), vec![1, 2, 4, 5, 6, 7, 8, 9]);
+        tree.delete_min();
+        tree.delete_max();
+        assert_eq!(tree.keys(), vec![2, 4, 5, 6, 7, 8]);
+        assert_eq!(tree.size(), 6);
+        assert_eq!(tree.check_invariants(), Ok(()));
+    }
+
+    // Addresses of all nodes in the tree
+    fn node_ptrs<V: Clone>(tree: &RandomizedBST<V>) -> Vec<*const RefCell<RawNode<V>>> {
+        let mut ptrs = Vec::new();
+        let mut stack: Vec<Node<V>> = tree.tree.root.iter().cloned().collect();
+        while let Some(node) = stack.pop() {
+            ptrs.push(Rc::as_ptr(&node.0));
+            stack.extend(node.get().left.clone());
+            stack.extend(node.get().right.clone());
+        }
+        ptrs.sort();
+        ptrs
+    }
+
+    #[test]
+    fn no_copy_without_snapshot() {
+        let mut tree = RandomizedBST::with_seed(9);
+        for key in 0..100 {
+            let before = node_ptrs(&tree);
+            tree.put(key, key);
+            tree.put(key / 2, key);
+            let after = node_ptrs(&tree);
+            // only the new node was allocated
+            assert_eq!(after.len(), before.len() + 1);
+            assert!(before.iter().all(|ptr| after.binary_search(ptr).is_ok()));
+        }
+
+        for key in (0..100).step_by(3) {
+            let before = node_ptrs(&tree);
+            tree.delete(key);
+            tree.delete(key + 1000);
+            let after = node_ptrs(&tree);
+            assert_eq!(after.len(), before.len() - 1);
+            assert!(after.iter().all(|ptr| before.binary_search(ptr).is_ok()));
+        }
+        assert_eq!(tree.check_invariants(), Ok(()));
+
+        // with a snapshot, deleting a missing key copies nothing either
+        let snapshot = tree.tree.snapshot();
+        let before = node_ptrs(&tree);
+        tree.delete(1000);
+        assert_eq!(node_ptrs(&tree), before);
+        drop(snapshot);
+    }
+
+    #[test]
+    fn iterator_keeps_old_version() {
+        let mut tree = RandomizedBST::with_seed(5);
+        for key in 0..10 {
+            tree.put(key, key);
+        }
+
+        let iter = tree.iter();
+        tree.delete(4);
+        tree.put(20, 20);
+
+        assert_eq!(iter.map(|(key, _)| key).collect::<Vec<_>>(),
+            (0..10).collect::<Vec<_>>());
+        assert_eq!(tree.size(), 10);
+    }
+}
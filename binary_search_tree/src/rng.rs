@@ -0,0 +1,43 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+/// A small xorshift64* pseudo-random number generator.
+///
+/// It is not suitable for cryptography, but it is fast and seedable, which is
+/// all the randomized trees need.
+#[derive(Debug, Clone)]
+pub(crate) struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// Creates a generator whose sequence is fully determined by `seed`.
+    pub(crate) fn with_seed(seed: u64) -> Self {
+        // Scramble the seed with splitmix64 so that small seeds like 0 and 1
+        // still give well-mixed, non-zero states.
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        Rng { state: if z == 0 { 1 } else { z } }
+    }
+
+    /// Creates a generator seeded from the standard library's per-process
+    /// random keys.
+    pub(crate) fn from_entropy() -> Self {
+        let seed = RandomState::new().build_hasher().finish();
+        Rng::with_seed(seed)
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Returns a number in the range `0..n`. `n` must not be zero.
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
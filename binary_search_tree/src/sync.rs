@@ -0,0 +1,439 @@
+use std::mem;
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+
+type Link<V> = Option<Arc<SyncNode<V>>>;
+
+#[derive(Debug)]
+struct SyncNode<V> {
+    key: usize,
+    value: V,
+    left: Link<V>,
+    right: Link<V>,
+    size: usize,
+}
+
+impl<V> SyncNode<V> {
+    fn new(key: usize, value: V, left: Link<V>, right: Link<V>) -> Arc<Self> {
+        let size = 1 + SyncNode::size(&left) + SyncNode::size(&right);
+        Arc::new(SyncNode { key, value, left, right, size })
+    }
+
+    fn size(node: &Link<V>) -> usize {
+        node.as_ref().map_or(0, |n| n.size)
+    }
+}
+
+impl<V> Drop for SyncNode<V> {
+    fn drop(&mut self) {
+        // Same as `Node`: free the nodes nothing else refers to one at a time
+        // so that a degenerate tree doesn't overflow the stack.
+        let mut stack: Vec<Arc<SyncNode<V>>> = Vec::new();
+        stack.extend(self.left.take());
+        stack.extend(self.right.take());
+        while let Some(node) = stack.pop() {
+            if let Some(mut node) = Arc::into_inner(node) {
+                stack.extend(node.left.take());
+                stack.extend(node.right.take());
+            }
+        }
+    }
+}
+
+/// A Binary Search Tree that can be shared between threads.
+///
+/// Nodes are immutable and reference counted with `Arc`. A write copies the
+/// nodes on the path it changes, the same way `BST` does for snapshots, and
+/// then swaps in the new root. Readers only hold a lock for as long as it
+/// takes to clone the root, so they run concurrently with each other and
+/// with the writer, and each read sees the whole tree as it was before or
+/// after any write, never in between. Writers are serialized.
+///
+/// To share the tree, put it in an `Arc`.
+#[derive(Debug)]
+pub struct SyncBST<V> {
+    root: RwLock<Link<V>>,
+    writer: Mutex<()>,
+}
+
+impl<V: Clone> Default for SyncBST<V> {
+    fn default() -> Self {
+        SyncBST::new()
+    }
+}
+
+impl<V: Clone> SyncBST<V> {
+    /// Creates a new empty tree.
+    pub fn new() -> Self {
+        SyncBST {
+            root: RwLock::new(None),
+            writer: Mutex::new(()),
+        }
+    }
+
+    // A writer never leaves a half-modified tree behind, even if it panics,
+    // so a poisoned lock is safe to keep using.
+
+    fn current_root(&self) -> Link<V> {
+        self.root.read().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// Runs `write` with the current root while holding the writer lock and
+    /// installs the root it returns, if any.
+    fn update<F>(&self, write: F)
+        where F: FnOnce(&Link<V>) -> Option<Link<V>>
+    {
+        let _writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        let root = self.current_root();
+        if let Some(new_root) = write(&root) {
+            let mut guard = self.root.write().unwrap_or_else(PoisonError::into_inner);
+            let old_root = mem::replace(&mut *guard, new_root);
+            drop(guard);
+            // Free the replaced nodes after readers are let back in.
+            drop(old_root);
+        }
+    }
+
+    /// Returns `true` if the tree has no node elements.
+    pub fn is_empty(&self) -> bool {
+        self.size() == 0
+    }
+
+    /// Returns the number of elements in the tree.
+    pub fn size(&self) -> usize {
+        SyncNode::size(&self.current_root())
+    }
+
+    pub fn contains(&self, key: usize) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Returns a clone of the value associated with the given key.
+    pub fn get(&self, key: usize) -> Option<V> {
+        let root = self.current_root();
+        let mut x = &root;
+        while let Some(node) = x {
+            if key < node.key {
+                x = &node.left;
+            } else if key > node.key {
+                x = &node.right;
+            } else {
+                return Some(node.value.clone());
+            }
+        }
+        None
+    }
+
+    /// Inserts the given key-value pair into the tree. If the tree already
+    /// contains the given key, the associated value is updated.
+    pub fn put(&self, key: usize, value: V) {
+        self.update(|root| {
+            let mut path = Vec::new();
+            let mut x = root;
+            while let Some(node) = x {
+                if key < node.key {
+                    path.push((node, true));
+                    x = &node.left;
+                } else if key > node.key {
+                    path.push((node, false));
+                    x = &node.right;
+                } else {
+                    let left = node.left.clone();
+                    let right = node.right.clone();
+                    let new_node = SyncNode::new(key, value, left, right);
+                    return Some(SyncBST::rebuild(path, Some(new_node)));
+                }
+            }
+
+            let new_node = SyncNode::new(key, value, None, None);
+            Some(SyncBST::rebuild(path, Some(new_node)))
+        });
+    }
+
+    /// Removes the smallest key and its associated value from the tree.
+    pub fn delete_min(&self) {
+        self.update(|root| {
+            let mut path = Vec::new();
+            let mut node = root.as_ref()?;
+            while let Some(left) = &node.left {
+                path.push((node, true));
+                node = left;
+            }
+            Some(SyncBST::rebuild(path, node.right.clone()))
+        });
+    }
+
+    /// Removes the largest key and its associated value from the tree.
+    pub fn delete_max(&self) {
+        self.update(|root| {
+            let mut path = Vec::new();
+            let mut node = root.as_ref()?;
+            while let Some(right) = &node.right {
+                path.push((node, false));
+                node = right;
+            }
+            Some(SyncBST::rebuild(path, node.left.clone()))
+        });
+    }
+
+    /// Removes the given key and its associated value from the tree.
+    pub fn delete(&self, key: usize) {
+        self.update(|root| {
+            let mut path = Vec::new();
+            let mut node = root.as_ref()?;
+            while key != node.key {
+                if key < node.key {
+                    path.push((node, true));
+                    node = node.left.as_ref()?;
+                } else {
+                    path.push((node, false));
+                    node = node.right.as_ref()?;
+                }
+            }
+
+            let replacement = match (&node.left, &node.right) {
+                (None, right) => right.clone(),
+                (left, None) => left.clone(),
+                (left, Some(right)) => {
+                    // minimum of the right replaces node to be deleted
+                    let mut min_path = Vec::new();
+                    let mut min = right;
+                    while let Some(next) = &min.left {
+                        min_path.push((min, true));
+                        min = next;
+                    }
+                    let new_right = SyncBST::rebuild(min_path, min.right.clone());
+                    let value = min.value.clone();
+                    Some(SyncNode::new(min.key, value, left.clone(), new_right))
+                }
+            };
+            Some(SyncBST::rebuild(path, replacement))
+        });
+    }
+
+    /// Copies the nodes of `path` from the bottom up. Each copy gets the copy
+    /// below it, or `subtree` at the very bottom, in place of the child the
+    /// path went through: the left one if the `bool` of that step is `true`.
+    /// Returns the new root.
+    fn rebuild(path: Vec<(&Arc<SyncNode<V>>, bool)>, subtree: Link<V>) -> Link<V> {
+        let mut subtree = subtree;
+        for (node, went_left) in path.into_iter().rev() {
+            let (left, right) = if went_left {
+                (subtree, node.right.clone())
+            } else {
+                (node.left.clone(), subtree)
+            };
+            subtree = Some(SyncNode::new(node.key, node.value.clone(), left, right));
+        }
+        subtree
+    }
+
+    /// Returns the smallest key in the tree.
+    pub fn min(&self) -> Option<usize> {
+        let root = self.current_root();
+        let mut node = root.as_ref()?;
+        while let Some(left) = &node.left {
+            node = left;
+        }
+        Some(node.key)
+    }
+
+    /// Returns the largest key in the tree.
+    pub fn max(&self) -> Option<usize> {
+        let root = self.current_root();
+        let mut node = root.as_ref()?;
+        while let Some(right) = &node.right {
+            node = right;
+        }
+        Some(node.key)
+    }
+
+    /// Returns all the keys of the tree, in order, as of a single point in
+    /// time.
+    pub fn keys(&self) -> Vec<usize> {
+        self.entries().into_iter().map(|(key, _)| key).collect()
+    }
+
+    /// Returns clones of all the entries of the tree, in key order, as of a
+    /// single point in time.
+    pub fn entries(&self) -> Vec<(usize, V)> {
+        let root = self.current_root();
+        let mut entries = Vec::with_capacity(SyncNode::size(&root));
+        let mut stack = Vec::new();
+        let mut x = &root;
+        loop {
+            while let Some(node) = x {
+                stack.push(node);
+                x = &node.left;
+            }
+            match stack.pop() {
+                Some(node) => {
+                    entries.push((node.key, node.value.clone()));
+                    x = &node.right;
+                }
+                None => return entries,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+
+    fn check_sizes<V>(x: &Link<V>) -> usize {
+        match x {
+            Some(node) => {
+                let size = 1 + check_sizes(&node.left) + check_sizes(&node.right);
+                assert_eq!(node.size, size);
+                size
+            }
+            None => 0,
+        }
+    }
+
+    #[test]
+    fn send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<SyncBST<String>>();
+    }
+
+    #[test]
+    fn single_thread() {
+        let bst = SyncBST::new();
+        assert!(bst.is_empty());
+        assert_eq!(bst.min(), None);
+        bst.delete(1);
+        bst.delete_min();
+        bst.delete_max();
+
+        for key in &[8, 3, 1, 7, 2, 5, 9, 6, 4] {
+            bst.put(*key, key.to_string());
+        }
+        assert_eq!(bst.size(), 9);
+        assert_eq!(bst.keys(), vec![1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(bst.get(5), Some("5".to_string()));
+        assert!(!bst.contains(10));
+
+        bst.put(5, "five".to_string());
+        assert_eq!(bst.get(5), Some("five".to_string()));
+        assert_eq!(bst.size(), 9);
+
+        bst.delete(3);
+        bst.delete(8);
+        bst.delete(10);
+        bst.delete_min();
+        bst.delete_max();
+        assert_eq!(bst.keys(), vec![2, 4, 5, 6, 7]);
+        assert_eq!(bst.min(), Some(2));
+        assert_eq!(bst.max(), Some(7));
+        assert_eq!(check_sizes(&bst.current_root()), 5);
+    }
+
+    #[test]
+    fn readers_keep_old_version() {
+        let bst = SyncBST::new();
+        for key in 0..10 {
+            bst.put(key, key);
+        }
+        let old_root = bst.current_root();
+
+        bst.delete(4);
+        bst.put(4, 40);
+        bst.put(20, 20);
+
+        assert_eq!(check_sizes(&old_root), 10);
+        assert_eq!(bst.get(4), Some(40));
+        assert_eq!(bst.size(), 11);
+    }
+
+    #[test]
+    fn deep_tree() {
+        let bst = SyncBST::new();
+        let n = 2000;
+        for key in 0..n {
+            bst.put(key, key);
+        }
+        assert_eq!(bst.size(), n);
+        assert_eq!(bst.max(), Some(n - 1));
+        bst.delete(n / 2);
+        assert_eq!(bst.entries().len(), n - 1);
+    }
+
+    #[test]
+    fn concurrent_readers_and_writers() {
+        let writers = 4;
+        let per_writer = 500;
+        let bst = SyncBST::new();
+        let done = AtomicBool::new(false);
+
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    let mut rounds = 0;
+                    while !done.load(Ordering::Relaxed) || rounds == 0 {
+                        // every read sees one consistent version
+                        let entries = bst.entries();
+                        assert!(entries.windows(2).all(|w| w[0].0 < w[1].0));
+                        assert!(entries.iter().all(|(key, value)| *value == key * 2));
+                        for key in (0..writers * per_writer).step_by(97) {
+                            if let Some(value) = bst.get(key) {
+                                assert_eq!(value, key * 2);
+                            }
+                        }
+                        rounds += 1;
+                    }
+                });
+            }
+
+            let handles: Vec<_> = (0..writers).map(|w| {
+                let bst = &bst;
+                s.spawn(move || {
+                    for i in 0..per_writer {
+                        let key = i * writers + w;
+                        bst.put(key, key * 2);
+                    }
+                })
+            }).collect();
+            for handle in handles {
+                handle.join().unwrap();
+            }
+            done.store(true, Ordering::Relaxed);
+        });
+
+        let n = writers * per_writer;
+        assert_eq!(bst.size(), n);
+        assert_eq!(bst.keys(), (0..n).collect::<Vec<_>>());
+        assert_eq!(check_sizes(&bst.current_root()), n);
+    }
+
+    #[test]
+    fn concurrent_deletes() {
+        let n = 2000;
+        let bst = Arc::new(SyncBST::new());
+        for key in (0..n).rev().step_by(2).chain((0..n).step_by(2)) {
+            bst.put(key, key);
+        }
+        assert_eq!(bst.size(), n);
+
+        let handles: Vec<_> = (0..4).map(|t| {
+            let bst = Arc::clone(&bst);
+            thread::spawn(move || {
+                for key in (t..n).step_by(4) {
+                    if key % 3 != 0 {
+                        bst.delete(key);
+                    }
+                    assert!(bst.size() >= n / 3);
+                }
+            })
+        }).collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let expected: Vec<usize> = (0..n).filter(|key| key % 3 == 0).collect();
+        assert_eq!(bst.keys(), expected);
+        assert_eq!(check_sizes(&bst.current_root()), expected.len());
+    }
+}
@@ -0,0 +1,58 @@
+use crate::rng::Rng;
+use crate::BST;
+
+// The keys `populate_tree` puts, in order. They build this tree:
+//
+//          8
+//         / \
+//        3   9
+//       / \
+//      1   7
+//       \  /
+//       2 5
+//        / \
+//       4   6
+pub(crate) const KEYS: [usize; 9] = [8, 3, 1, 7, 2, 5, 9, 6, 4];
+
+/// Builds the tree of `KEYS`, with `value(key)` as the value of every key.
+pub(crate) fn populate_tree<V: Clone>(value: impl Fn(usize) -> V) -> BST<V> {
+    let mut bst = BST::new();
+    for key in KEYS {
+        bst.put(key, value(key));
+    }
+    bst
+}
+
+/// An operation on a tree and on its model, with the weight it is drawn
+/// with by `random_operations`.
+pub(crate) type WeightedOp<S> = (usize, fn(&mut S, &mut Rng));
+
+/// Runs a random sequence of `steps` operations from `ops` for every seed in
+/// `0..seeds`, on the tree and model that `new` makes for the seed. `check`
+/// compares them after every operation, and `finish` at the end.
+pub(crate) fn random_operations<S>(
+    seeds: u64,
+    steps: usize,
+    new: impl Fn(u64) -> S,
+    ops: &[WeightedOp<S>],
+    check: impl Fn(&S, &mut Rng),
+    finish: impl Fn(S),
+) {
+    let total: usize = ops.iter().map(|&(weight, _)| weight).sum();
+    for seed in 0..seeds {
+        let mut rng = Rng::with_seed(seed);
+        let mut state = new(seed);
+        for _ in 0..steps {
+            let mut draw = rng.below(total);
+            for &(weight, op) in ops {
+                if draw < weight {
+                    op(&mut state, &mut rng);
+                    break;
+                }
+                draw -= weight;
+            }
+            check(&state, &mut rng);
+        }
+        finish(state);
+    }
+}
@@ -0,0 +1,243 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::{Color, NodeId, RedBlackTree};
+
+// A broken invariant found by `check_invariants`. Nodes are identified by
+// their rank, the position of their key in key order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvariantViolation {
+    // The key of the node is not greater than the key before it.
+    Ordering { rank: usize },
+    // The root is red.
+    RedRoot,
+    // The node has a red right child, so the tree doesn't lean left.
+    RightRed { rank: usize },
+    // The node and its left child are both red.
+    DoubleRed { rank: usize },
+    // The paths down the two children of the node have different numbers
+    // of black nodes.
+    BlackHeight { rank: usize, left: usize, right: usize },
+    // The `size` of the node is not 1 plus the sizes of its children.
+    Size { rank: usize, expected: usize, found: usize },
+    // The arena holds nodes that are not in the tree.
+    Arena { nodes: usize, size: usize },
+}
+
+impl fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvariantViolation::Ordering { rank } => {
+                write!(f, "key at rank {} is not greater than the key before it", rank)
+            }
+            InvariantViolation::RedRoot => write!(f, "root is red"),
+            InvariantViolation::RightRed { rank } => {
+                write!(f, "node at rank {} has a red right child", rank)
+            }
+            InvariantViolation::DoubleRed { rank } => {
+                write!(f, "node at rank {} and its left child are both red", rank)
+            }
+            InvariantViolation::BlackHeight { rank, left, right } => {
+                write!(f, "node at rank {} has black height {} on the left and {} on the right",
+                    rank, left, right)
+            }
+            InvariantViolation::Size { rank, expected, found } => {
+                write!(f, "node at rank {} has size {} but its subtree has {} nodes",
+                    rank, found, expected)
+            }
+            InvariantViolation::Arena { nodes, size } => {
+                write!(f, "arena holds {} nodes but the tree has {}", nodes, size)
+            }
+        }
+    }
+}
+
+impl Error for InvariantViolation {}
+
+impl<K: Ord, V> RedBlackTree<K, V> {
+    // Checks the order of the keys, the left-leaning red-black invariants,
+    // the sizes and that every node of the arena is in the tree, and returns
+    // the first violation in key order
+    pub fn check_invariants(&self) -> Result<(), InvariantViolation> {
+        if self.is_red(self.root) {
+            return Err(InvariantViolation::RedRoot);
+        }
+
+        let mut rank = 0;
+        let mut prev = None;
+        self.check_node(self.root, &mut rank, &mut prev)?;
+
+        if rank != self.nodes.len() {
+            return Err(InvariantViolation::Arena { nodes: self.nodes.len(), size: rank });
+        }
+        Ok(())
+    }
+
+    // Checks the subtree in order, counting the nodes in `rank`, and returns
+    // its black height. The recursion goes as deep as the tree, which is
+    // O(log n) unless the tree is already broken.
+    fn check_node<'a>(
+        &'a self,
+        node: Option<NodeId>,
+        rank: &mut usize,
+        prev: &mut Option<&'a K>,
+    ) -> Result<usize, InvariantViolation> {
+        let node_id = match node {
+            Some(node_id) => node_id,
+            None => return Ok(0),
+        };
+        let node = &self.nodes[node_id];
+
+        let left = self.check_node(node.left, rank, prev)?;
+        let node_rank = *rank;
+        if prev.is_some_and(|prev| *prev >= node.key) {
+            return Err(InvariantViolation::Ordering { rank: node_rank });
+        }
+        *prev = Some(&node.key);
+        *rank += 1;
+        let right = self.check_node(node.right, rank, prev)?;
+
+        if self.is_red(node.right) {
+            return Err(InvariantViolation::RightRed { rank: node_rank });
+        }
+        if node.color == Color::Red && self.is_red(node.left) {
+            return Err(InvariantViolation::DoubleRed { rank: node_rank });
+        }
+        if left != right {
+            return Err(InvariantViolation::BlackHeight { rank: node_rank, left, right });
+        }
+        let expected = 1 + self.size_of(node.left) + self.size_of(node.right);
+        if node.size != expected {
+            return Err(InvariantViolation::Size { rank: node_rank, expected, found: node.size });
+        }
+
+        match node.color {
+            Color::Black => Ok(left + 1),
+            Color::Red => Ok(left),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::XorShift;
+    use crate::Entry;
+    use std::collections::BTreeMap;
+
+    fn populate_tree() -> RedBlackTree<usize, usize> {
+        (0..10).map(|key| (key, key)).collect()
+    }
+
+    #[test]
+    fn check_invariants() {
+        assert_eq!(RedBlackTree::<usize, usize>::new().check_invariants(), Ok(()));
+        assert_eq!(populate_tree().check_invariants(), Ok(()));
+
+        let mut tree = populate_tree();
+        let root_id = tree.root.unwrap();
+        tree.nodes[root_id].color = Color::Red;
+        assert_eq!(tree.check_invariants(), Err(InvariantViolation::RedRoot));
+
+        let mut tree = populate_tree();
+        let root_id = tree.root.unwrap();
+        let rank = tree.rank(&tree.nodes[root_id].key);
+        tree.nodes[root_id].size += 1;
+        let violation = InvariantViolation::Size { rank, expected: 10, found: 11 };
+        assert_eq!(tree.check_invariants(), Err(violation));
+
+        // the smallest key is a leaf
+        let mut tree = populate_tree();
+        let min_id = tree.find(&0).unwrap();
+        tree.nodes[min_id].key = 5;
+        assert_eq!(tree.check_invariants(), Err(InvariantViolation::Ordering { rank: 1 }));
+
+        // 8 is the red left child of 9
+        let mut tree = populate_tree();
+        let id = tree.find(&8).unwrap();
+        tree.nodes[id].color = Color::Black;
+        let violation = InvariantViolation::BlackHeight { rank: 9, left: 1, right: 0 };
+        assert_eq!(tree.check_invariants(), Err(violation));
+
+        let mut tree = populate_tree();
+        let id = tree.find(&9).unwrap();
+        tree.nodes[id].color = Color::Red;
+        assert_eq!(tree.check_invariants(), Err(InvariantViolation::DoubleRed { rank: 9 }));
+
+        // 6 is the right child of 5
+        let mut tree = populate_tree();
+        let id = tree.find(&6).unwrap();
+        tree.nodes[id].color = Color::Red;
+        assert_eq!(tree.check_invariants(), Err(InvariantViolation::RightRed { rank: 5 }));
+
+        let mut tree = populate_tree();
+        tree.nodes.push(tree.nodes[0].clone());
+        let violation = InvariantViolation::Arena { nodes: 11, size: 10 };
+        assert_eq!(tree.check_invariants(), Err(violation.clone()));
+        assert_eq!(violation.to_string(), "arena holds 11 nodes but the tree has 10");
+    }
+
+    // Runs random operations on a tree and on a `BTreeMap`, and checks that
+    // they agree and that the invariants hold after every operation.
+    #[test]
+    fn differential() {
+        for seed in 1..=50 {
+            let mut rng = XorShift(seed);
+            let mut tree = RedBlackTree::new();
+            let mut model = BTreeMap::new();
+
+            for step in 0..500 {
+                let key = rng.below(100);
+                match rng.below(12) {
+                    0..=3 => {
+                        tree.put(key, step);
+                        model.insert(key, step);
+                    }
+                    4 | 5 => assert_eq!(tree.remove(&key), model.remove(&key)),
+                    6 => assert_eq!(tree.pop_first(), model.pop_first()),
+                    7 => assert_eq!(tree.pop_last(), model.pop_last()),
+                    8 => {
+                        tree.delete_min();
+                        tree.delete_max();
+                        model.pop_first();
+                        model.pop_last();
+                    }
+                    9 => match tree.entry(key) {
+                        Entry::Occupied(entry) => {
+                            assert_eq!(entry.remove_entry(), model.remove_entry(&key).unwrap());
+                        }
+                        Entry::Vacant(entry) => {
+                            entry.insert(step);
+                            model.insert(key, step);
+                        }
+                    },
+                    10 => {
+                        if let Some(value) = tree.get_mut(&key) {
+                            *value += 1;
+                        }
+                        if let Some(value) = model.get_mut(&key) {
+                            *value += 1;
+                        }
+                    }
+                    _ => {
+                        tree.delete(&key);
+                        model.remove(&key);
+                    }
+                }
+
+                assert_eq!(tree.check_invariants(), Ok(()));
+                assert_eq!(tree.size(), model.len());
+                assert_eq!(tree.get(&key), model.get(&key));
+                assert_eq!(tree.floor(&key), model.range(..=key).next_back().map(|(k, _)| k));
+                assert_eq!(tree.ceiling(&key), model.range(key..).next().map(|(k, _)| k));
+                assert_eq!(tree.rank(&key), model.range(..key).count());
+            }
+
+            assert!(tree.iter().eq(model.iter()));
+            assert!(tree.range(20..60).rev().eq(model.range(20..60).rev()));
+            for (rank, key) in model.keys().enumerate() {
+                assert_eq!(tree.select(rank), Some(key));
+            }
+        }
+    }
+}
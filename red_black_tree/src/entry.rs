@@ -0,0 +1,269 @@
+use std::cmp::Ordering;
+use std::mem;
+
+use crate::{NodeId, RedBlackTree};
+
+// An entry of the tree, vacant or occupied, as returned by `entry`
+pub enum Entry<'a, K: Ord, V> {
+    Vacant(VacantEntry<'a, K, V>),
+    Occupied(OccupiedEntry<'a, K, V>),
+}
+
+pub struct VacantEntry<'a, K: Ord, V> {
+    key: K,
+    // the nodes walked down from the root, and the side the key went
+    path: Vec<(NodeId, Ordering)>,
+    tree: &'a mut RedBlackTree<K, V>,
+}
+
+pub struct OccupiedEntry<'a, K: Ord, V> {
+    node_id: NodeId,
+    tree: &'a mut RedBlackTree<K, V>,
+}
+
+impl<'a, K: Ord, V> Entry<'a, K, V> {
+    pub(crate) fn new(tree: &'a mut RedBlackTree<K, V>, key: K) -> Self {
+        let mut path = Vec::new();
+        let mut x = tree.root;
+        while let Some(node_id) = x {
+            let node = &tree.nodes[node_id];
+            let side = key.cmp(&node.key);
+            x = match side {
+                Ordering::Less => node.left,
+                Ordering::Greater => node.right,
+                Ordering::Equal => return Entry::Occupied(OccupiedEntry { node_id, tree }),
+            };
+            path.push((node_id, side));
+        }
+        Entry::Vacant(VacantEntry { key, path, tree })
+    }
+
+    // Inserts the default if the entry is vacant, and returns the value
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default),
+        }
+    }
+
+    // Inserts the result of default if the entry is vacant, and returns the
+    // value
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    // Like `or_insert_with`, with the key passed to default
+    pub fn or_insert_with_key<F: FnOnce(&K) -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let value = default(&entry.key);
+                entry.insert(value)
+            }
+        }
+    }
+
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    // Calls f on the value if the entry is occupied
+    pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        match self {
+            Entry::Occupied(mut entry) => {
+                f(entry.get_mut());
+                Entry::Occupied(entry)
+            }
+            Entry::Vacant(entry) => Entry::Vacant(entry),
+        }
+    }
+}
+
+impl<'a, K: Ord, V: Default> Entry<'a, K, V> {
+    // Inserts `V::default()` if the entry is vacant, and returns the value
+    pub fn or_default(self) -> &'a mut V {
+        self.or_insert_with(Default::default)
+    }
+}
+
+impl<'a, K: Ord, V> VacantEntry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    // Puts the key with the value, and returns the value
+    pub fn insert(self, value: V) -> &'a mut V {
+        let node_id = self.tree.put_below(&self.path, self.key, value);
+        &mut self.tree.nodes[node_id].value
+    }
+}
+
+impl<'a, K: Ord, V> OccupiedEntry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        &self.tree.nodes[self.node_id].key
+    }
+
+    pub fn get(&self) -> &V {
+        &self.tree.nodes[self.node_id].value
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.tree.nodes[self.node_id].value
+    }
+
+    // The value, borrowed for as long as the tree
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.tree.nodes[self.node_id].value
+    }
+
+    // Replaces the value, and returns the old one
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    pub fn remove_entry(self) -> (K, V) {
+        self.tree.remove_node(self.node_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counter() {
+        let mut tree = RedBlackTree::new();
+        for word in "the quick fox jumps over the lazy dog and the fox".split(' ') {
+            *tree.entry(word).or_insert(0) += 1;
+        }
+
+        assert_eq!(tree.get(&"the"), Some(&3));
+        assert_eq!(tree.get(&"fox"), Some(&2));
+        assert_eq!(tree.get(&"dog"), Some(&1));
+        assert_eq!(tree.size(), 8);
+        assert_eq!(tree.nodes.len(), 8);
+    }
+
+    #[test]
+    fn or_insert_with() {
+        let mut tree = RedBlackTree::new();
+        tree.put(2, "b".to_string());
+
+        let mut calls = 0;
+        tree.entry(2).or_insert_with(|| { calls += 1; "x".to_string() });
+        tree.entry(1).or_insert_with(|| { calls += 1; "a".to_string() });
+        tree.entry(3).or_insert_with_key(|key| key.to_string()).push('!');
+
+        assert_eq!(calls, 1);
+        assert_eq!(tree.get(&1), Some(&"a".to_string()));
+        assert_eq!(tree.get(&2), Some(&"b".to_string()));
+        assert_eq!(tree.get(&3), Some(&"3!".to_string()));
+
+        let mut tree: RedBlackTree<usize, Vec<usize>> = RedBlackTree::new();
+        tree.entry(4).or_default().push(1);
+        tree.entry(4).or_default().push(2);
+        assert_eq!(tree.get(&4), Some(&vec![1, 2]));
+    }
+
+    #[test]
+    fn and_modify() {
+        let mut tree = RedBlackTree::new();
+        tree.entry(1).and_modify(|v| *v += 1).or_insert(10);
+        assert_eq!(tree.get(&1), Some(&10));
+        tree.entry(1).and_modify(|v| *v += 1).or_insert(10);
+        assert_eq!(tree.get(&1), Some(&11));
+    }
+
+    #[test]
+    fn occupied_and_vacant() {
+        let mut tree = RedBlackTree::new();
+        for key in 0..10 {
+            tree.put(key, key * 10);
+        }
+
+        match tree.entry(5) {
+            Entry::Occupied(mut entry) => {
+                assert_eq!(entry.key(), &5);
+                assert_eq!(entry.get(), &50);
+                assert_eq!(entry.insert(55), 50);
+                *entry.get_mut() += 1;
+                assert_eq!(entry.get(), &56);
+            }
+            Entry::Vacant(_) => panic!("5 is in the tree"),
+        }
+        assert_eq!(tree.get(&5), Some(&56));
+
+        // inserting rebalances the tree, but the reference still points at
+        // the new value
+        match tree.entry(10) {
+            Entry::Occupied(_) => panic!("10 is not in the tree"),
+            Entry::Vacant(entry) => {
+                assert_eq!(entry.key(), &10);
+                *entry.insert(100) += 1;
+            }
+        }
+        assert_eq!(tree.get(&10), Some(&101));
+        assert_eq!(tree.size(), 11);
+        assert_eq!(tree.check_invariants(), Ok(()));
+
+        match tree.entry(11) {
+            Entry::Vacant(entry) => assert_eq!(entry.into_key(), 11),
+            Entry::Occupied(_) => panic!("11 is not in the tree"),
+        }
+        assert_eq!(tree.size(), 11);
+    }
+
+    #[test]
+    fn remove() {
+        let mut tree = RedBlackTree::new();
+        for key in 0..10 {
+            tree.put(key, key * 10);
+        }
+
+        if let Entry::Occupied(entry) = tree.entry(3) {
+            assert_eq!(entry.remove_entry(), (3, 30));
+        }
+        if let Entry::Occupied(entry) = tree.entry(8) {
+            assert_eq!(entry.remove(), 80);
+        }
+
+        let keys: Vec<usize> = tree.keys().cloned().collect();
+        assert_eq!(keys, vec![0, 1, 2, 4, 5, 6, 7, 9]);
+        assert_eq!(tree.nodes.len(), 8);
+        assert_eq!(tree.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn insert_and_remove_in_order() {
+        let mut tree = RedBlackTree::new();
+        for key in (0..100).chain((200..300).rev()).chain(100..200) {
+            if let Entry::Vacant(entry) = tree.entry(key) {
+                entry.insert(key);
+            }
+            assert_eq!(tree.check_invariants(), Ok(()));
+        }
+        assert!(tree.iter().map(|(k, v)| (*k, *v)).eq((0..300).map(|key| (key, key))));
+
+        for key in (0..300).step_by(2) {
+            if let Entry::Occupied(entry) = tree.entry(key) {
+                assert_eq!(entry.remove_entry(), (key, key));
+            }
+            assert_eq!(tree.check_invariants(), Ok(()));
+        }
+        assert!(tree.keys().cloned().eq((1..300).step_by(2)));
+    }
+}
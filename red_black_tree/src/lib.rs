@@ -1,10 +1,28 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::iter::FromIterator;
+use std::marker::PhantomData;
+use std::ops::{Bound, Index, RangeBounds};
+use std::ptr;
+use std::vec;
+
+mod diagnostics;
+mod entry;
+mod set_ops;
+#[cfg(test)]
+mod test_util;
+
+pub use diagnostics::InvariantViolation;
+pub use entry::{Entry, OccupiedEntry, VacantEntry};
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Color {
     Red,
     Black,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Node<K, V> {
     key: K,
     value: V,
@@ -29,33 +47,26 @@ impl<K, V> Node<K, V> {
     }
 }
 
+// A `Range` over the whole tree
 pub struct TreeIter<'a, K: 'a, V: 'a> {
-    stack: Vec<NodeId>,
-    tree: &'a RedBlackTree<K, V>,
-}
-
-impl<'a, K: 'a, V: 'a> TreeIter<'a, K, V> {
-    fn push_left_edge(&mut self, mut node: Option<NodeId>) {
-        while let Some(node_id) = node {
-            self.stack.push(node_id);
-            node = self.tree.nodes[node_id].left;
-        }
-    }
+    inner: Range<'a, K, V>,
 }
 
 impl<'a, K, V> Iterator for TreeIter<'a, K, V> {
     type Item = (&'a K, &'a V);
 
     fn next(&mut self) -> Option<(&'a K, &'a V)> {
-        let node_id = match self.stack.pop() {
-            Some(n) => n,
-            None => return None,
-        };
+        self.inner.next()
+    }
 
-        self.push_left_edge(self.tree.nodes[node_id].right);
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
 
-        let node = &self.tree.nodes[node_id];
-        Some((&node.key, &node.value))
+impl<'a, K, V> DoubleEndedIterator for TreeIter<'a, K, V> {
+    fn next_back(&mut self) -> Option<(&'a K, &'a V)> {
+        self.inner.next_back()
     }
 }
 
@@ -69,18 +80,324 @@ impl<'a, K, V> Iterator for Keys<'a, K, V> {
     fn next(&mut self) -> Option<&'a K> {
         self.inner.next().map(|(k, _)| k)
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Keys<'a, K, V> {
+    fn next_back(&mut self) -> Option<&'a K> {
+        self.inner.next_back().map(|(k, _)| k)
+    }
 }
 
-#[derive(Debug)]
+pub struct Values<'a, K: 'a, V: 'a> {
+    inner: TreeIter<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<&'a V> {
+        self.inner.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Values<'a, K, V> {
+    fn next_back(&mut self) -> Option<&'a V> {
+        self.inner.next_back().map(|(_, v)| v)
+    }
+}
+
+pub struct IterMut<'a, K: 'a, V: 'a> {
+    // The stacks of `Range` over the whole tree. The arena is behind a
+    // pointer so that the values can be lent out one at a time while the
+    // links of the nodes are still read.
+    front: Vec<NodeId>,
+    back: Vec<NodeId>,
+    remaining: usize,
+    nodes: *mut Node<K, V>,
+    marker: PhantomData<&'a mut Node<K, V>>,
+}
+
+// SAFETY: `IterMut` only hands out `&K` and `&mut V`, as a `&mut` to the
+// arena would, so it can be sent to another thread when those can.
+unsafe impl<'a, K: Sync, V: Send> Send for IterMut<'a, K, V> {}
+
+// SAFETY: a shared `IterMut` can't reach the nodes at all, so sharing it is
+// no more than sharing the keys and values it borrows.
+unsafe impl<'a, K: Sync, V: Sync> Sync for IterMut<'a, K, V> {}
+
+impl<'a, K, V> IterMut<'a, K, V> {
+    fn left(&self, node_id: NodeId) -> Option<NodeId> {
+        // SAFETY: the id is in the arena, and only keys and values are lent
+        // out, so the links can be read.
+        unsafe { ptr::addr_of!((*self.nodes.add(node_id)).left).read() }
+    }
+
+    fn right(&self, node_id: NodeId) -> Option<NodeId> {
+        // SAFETY: as in `left`
+        unsafe { ptr::addr_of!((*self.nodes.add(node_id)).right).read() }
+    }
+
+    fn entry(&mut self, node_id: NodeId) -> (&'a K, &'a mut V) {
+        // SAFETY: `remaining` stops the stacks before they cross, so every
+        // node is lent out once, and the tree is mutably borrowed for 'a.
+        unsafe {
+            let node = self.nodes.add(node_id);
+            (&*ptr::addr_of!((*node).key), &mut *ptr::addr_of_mut!((*node).value))
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a mut V)> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+
+        let node_id = self.front.pop().unwrap();
+        let mut x = self.right(node_id);
+        while let Some(id) = x {
+            self.front.push(id);
+            x = self.left(id);
+        }
+        Some(self.entry(node_id))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for IterMut<'a, K, V> {
+    fn next_back(&mut self) -> Option<(&'a K, &'a mut V)> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+
+        let node_id = self.back.pop().unwrap();
+        let mut x = self.left(node_id);
+        while let Some(id) = x {
+            self.back.push(id);
+            x = self.right(id);
+        }
+        Some(self.entry(node_id))
+    }
+}
+
+pub struct ValuesMut<'a, K: 'a, V: 'a> {
+    inner: IterMut<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for ValuesMut<'a, K, V> {
+    type Item = &'a mut V;
+
+    fn next(&mut self) -> Option<&'a mut V> {
+        self.inner.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for ValuesMut<'a, K, V> {
+    fn next_back(&mut self) -> Option<&'a mut V> {
+        self.inner.next_back().map(|(_, v)| v)
+    }
+}
+
+pub struct Range<'a, K: 'a, V: 'a> {
+    // The top of `front` is the next node from the front and the top of
+    // `back` the next node from the back, like the stack of `TreeIter`
+    // mirrored for `back`. `remaining` keeps them from crossing.
+    front: Vec<NodeId>,
+    back: Vec<NodeId>,
+    remaining: usize,
+    tree: &'a RedBlackTree<K, V>,
+}
+
+impl<'a, K, V> Iterator for Range<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+
+        let node_id = self.front.pop().unwrap();
+        let mut x = self.tree.nodes[node_id].right;
+        while let Some(id) = x {
+            self.front.push(id);
+            x = self.tree.nodes[id].left;
+        }
+
+        let node = &self.tree.nodes[node_id];
+        Some((&node.key, &node.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Range<'a, K, V> {
+    fn next_back(&mut self) -> Option<(&'a K, &'a V)> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+
+        let node_id = self.back.pop().unwrap();
+        let mut x = self.tree.nodes[node_id].left;
+        while let Some(id) = x {
+            self.back.push(id);
+            x = self.tree.nodes[id].right;
+        }
+
+        let node = &self.tree.nodes[node_id];
+        Some((&node.key, &node.value))
+    }
+}
+
+// The entries are moved out of the arena in key order once, in O(n) time
+pub struct IntoIter<K, V> {
+    entries: vec::IntoIter<(K, V)>,
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        self.entries.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for IntoIter<K, V> {
+    fn next_back(&mut self) -> Option<(K, V)> {
+        self.entries.next_back()
+    }
+}
+
+// The arena can be cloned as is, since node ids stay valid in the copy.
+#[derive(Clone)]
 pub struct RedBlackTree<K, V> {
     root: Option<NodeId>,
     nodes: Vec<Node<K, V>>,
 }
 
-impl<K, V> RedBlackTree<K, V>
-    where K: PartialOrd + Clone,
-          V: Clone,
-{
+// Shows the entries in key order, like `BTreeMap`, rather than the arena
+impl<K: Ord + fmt::Debug, V: fmt::Debug> fmt::Debug for RedBlackTree<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: Ord, V> Default for RedBlackTree<K, V> {
+    fn default() -> Self {
+        RedBlackTree::new()
+    }
+}
+
+impl<K: Ord, V: PartialEq> PartialEq for RedBlackTree<K, V> {
+    fn eq(&self, other: &RedBlackTree<K, V>) -> bool {
+        self.size() == other.size() && self.iter().eq(other.iter())
+    }
+}
+
+impl<K: Ord, V: Eq> Eq for RedBlackTree<K, V> {}
+
+impl<K: Ord + Hash, V: Hash> Hash for RedBlackTree<K, V> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.size().hash(state);
+        for entry in self.iter() {
+            entry.hash(state);
+        }
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for RedBlackTree<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut tree = RedBlackTree::new();
+        tree.extend(iter);
+        tree
+    }
+}
+
+impl<K: Ord, V, const N: usize> From<[(K, V); N]> for RedBlackTree<K, V> {
+    fn from(entries: [(K, V); N]) -> Self {
+        IntoIterator::into_iter(entries).collect()
+    }
+}
+
+impl<K: Ord, V> Extend<(K, V)> for RedBlackTree<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.put(key, value);
+        }
+    }
+}
+
+impl<K: Ord, V> IntoIterator for RedBlackTree<K, V> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> IntoIter<K, V> {
+        let ids = self.inorder_ids();
+        let mut nodes: Vec<Option<Node<K, V>>> = self.nodes.into_iter().map(Some).collect();
+        let entries: Vec<(K, V)> = ids.into_iter()
+            .map(|node_id| {
+                let node = nodes[node_id].take().unwrap();
+                (node.key, node.value)
+            })
+            .collect();
+        IntoIter { entries: entries.into_iter() }
+    }
+}
+
+impl<'a, K: Ord, V> IntoIterator for &'a RedBlackTree<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = TreeIter<'a, K, V>;
+
+    fn into_iter(self) -> TreeIter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<'a, K: Ord, V> IntoIterator for &'a mut RedBlackTree<K, V> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> IterMut<'a, K, V> {
+        self.iter_mut()
+    }
+}
+
+impl<K: Ord, V> Index<&K> for RedBlackTree<K, V> {
+    type Output = V;
+
+    // Panics if the key is not in the tree
+    fn index(&self, key: &K) -> &V {
+        self.get(key).expect("no entry found for key")
+    }
+}
+
+impl<K: Ord, V> RedBlackTree<K, V> {
     pub fn new() -> Self {
         RedBlackTree {
             root: None,
@@ -114,21 +431,66 @@ impl<K, V> RedBlackTree<K, V>
         next_index
     }
 
-    pub fn get(&self, key: &K) -> Option<&V> {
-        let mut x = &self.root;
+    // Removes the node from the arena, which must already be unlinked from
+    // the tree. The last node of the arena moves into the freed slot, so the
+    // arena never holds more nodes than the tree.
+    fn free_node(&mut self, node_id: NodeId) -> Node<K, V> {
+        let last_id = self.nodes.len() - 1;
+        if node_id != last_id {
+            // relink the parent of the last node to its new slot
+            let key = &self.nodes[last_id].key;
+            let mut parent = None;
+            let mut x = self.root;
+            while let Some(x_id) = x {
+                if x_id == last_id {
+                    break;
+                }
+                parent = Some(x_id);
+                x = if *key < self.nodes[x_id].key {
+                    self.nodes[x_id].left
+                } else {
+                    self.nodes[x_id].right
+                };
+            }
+
+            match parent {
+                Some(parent_id) if self.nodes[parent_id].left == Some(last_id) => {
+                    self.nodes[parent_id].left = Some(node_id);
+                }
+                Some(parent_id) => self.nodes[parent_id].right = Some(node_id),
+                None => self.root = Some(node_id),
+            }
+        }
+
+        self.nodes.swap_remove(node_id)
+    }
+
+    fn find(&self, key: &K) -> Option<NodeId> {
+        let mut x = self.root;
         while let Some(node_id) = x {
-            let node = &self.nodes[*node_id];
-            if *key < node.key {
-                x = &node.left;
-            } else if *key > node.key {
-                x = &node.right;
-            } else {
-                return Some(&node.value);
+            let node = &self.nodes[node_id];
+            match key.cmp(&node.key) {
+                Ordering::Less => x = node.left,
+                Ordering::Greater => x = node.right,
+                Ordering::Equal => return Some(node_id),
             }
         }
         None
     }
 
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.find(key).map(|node_id| &self.nodes[node_id].value)
+    }
+
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let node_id = self.find(key)?;
+        Some(&mut self.nodes[node_id].value)
+    }
+
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V> {
+        Entry::new(self, key)
+    }
+
     pub fn contains(&self, key: &K) -> bool {
         self.get(key).is_some()
     }
@@ -143,12 +505,14 @@ impl<K, V> RedBlackTree<K, V>
 
     fn rput(&mut self, node: Option<NodeId>, key: K, value: V) -> Option<NodeId> {
         if let Some(mut node_id) = node {
-            if key < self.nodes[node_id].key {
-                self.nodes[node_id].left = self.rput(self.nodes[node_id].left, key, value);
-            } else if key > self.nodes[node_id].key {
-                self.nodes[node_id].right = self.rput(self.nodes[node_id].right, key, value);
-            } else {
-                self.nodes[node_id].value = value;
+            match key.cmp(&self.nodes[node_id].key) {
+                Ordering::Less => {
+                    self.nodes[node_id].left = self.rput(self.nodes[node_id].left, key, value);
+                }
+                Ordering::Greater => {
+                    self.nodes[node_id].right = self.rput(self.nodes[node_id].right, key, value);
+                }
+                Ordering::Equal => self.nodes[node_id].value = value,
             }
 
             // Fix any right-leaning links
@@ -178,26 +542,111 @@ impl<K, V> RedBlackTree<K, V>
         Some(node_id)
     }
 
-    pub fn delete_min(&mut self) {
-        if let Some(root_id) = self.root {
-            let left_is_red = self.is_red(self.nodes[root_id].left);
-            let right_is_red = self.is_red(self.nodes[root_id].right);
-            if !left_is_red && !right_is_red {
-                self.nodes[root_id].color = Color::Red;
+    // Links a new node below the last node of the path, on the side the key
+    // goes, and rebalances the path bottom-up like `rput`. The path is the
+    // one down from the root to where the key belongs, as walked by `entry`.
+    pub(crate) fn put_below(&mut self, path: &[(NodeId, Ordering)], key: K, value: V) -> NodeId {
+        let new_id = self.new_node(key, value, Color::Red);
+        let mut child = new_id;
+        for &(node_id, side) in path.iter().rev() {
+            match side {
+                Ordering::Less => self.nodes[node_id].left = Some(child),
+                _ => self.nodes[node_id].right = Some(child),
             }
+            child = self.balance(node_id);
+        }
 
-            self.root = self.rdelete_min(root_id);
-            if let Some(root_id) = self.root {
-                self.nodes[root_id].color = Color::Black;
+        self.nodes[child].color = Color::Black;
+        self.root = Some(child);
+        new_id
+    }
+
+    // Builds a tree from entries in increasing key order in O(n) time, with
+    // the last value winning for equal keys. Panics if a key is smaller than
+    // the one before it.
+    pub fn from_sorted_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut tree: RedBlackTree<K, V> = RedBlackTree::new();
+        for (key, value) in iter {
+            if let Some(last) = tree.nodes.last_mut() {
+                match last.key.cmp(&key) {
+                    Ordering::Less => {}
+                    Ordering::Equal => {
+                        last.value = value;
+                        continue;
+                    }
+                    Ordering::Greater => panic!("keys must be in increasing order"),
+                }
             }
+            tree.new_node(key, value, Color::Black);
         }
+
+        // The nodes are in key order in the arena. The largest black height
+        // that n nodes can fill is that of the largest perfect tree.
+        let n = tree.nodes.len();
+        tree.root = tree.link_sorted(0, n, (n + 1).ilog2());
+        tree
     }
 
-    fn rdelete_min(&mut self, mut node_id: NodeId) -> Option<NodeId> {
-        if self.nodes[node_id].left.is_none() {
+    // Links the n nodes of the arena starting at lo, which are in key order,
+    // into a subtree of the given black height. The subtree is laid out as a
+    // 2-3 tree, a 3-node being a black node with a red left child, so it
+    // holds between 2^height - 1 and 3^height - 1 nodes.
+    fn link_sorted(&mut self, lo: NodeId, n: usize, height: u32) -> Option<NodeId> {
+        if n == 0 {
             return None;
         }
 
+        let max_child = 3usize.saturating_pow(height - 1) - 1;
+        let node_id = if n - 1 <= max_child.saturating_mul(2) {
+            let left_size = (n - 1) / 2;
+            let node_id = lo + left_size;
+            self.nodes[node_id].left = self.link_sorted(lo, left_size, height - 1);
+            self.nodes[node_id].right = self.link_sorted(node_id + 1, n - 1 - left_size, height - 1);
+            node_id
+        } else {
+            // too many nodes for two children, so the root is a 3-node
+            let left_size = (n - 2) / 3;
+            let middle_size = (n - 2 - left_size) / 2;
+            let red_id = lo + left_size;
+            let node_id = red_id + middle_size + 1;
+            self.nodes[red_id].left = self.link_sorted(lo, left_size, height - 1);
+            self.nodes[red_id].right = self.link_sorted(red_id + 1, middle_size, height - 1);
+            self.nodes[red_id].color = Color::Red;
+            self.update_size_for(red_id);
+            self.nodes[node_id].left = Some(red_id);
+            self.nodes[node_id].right = self.link_sorted(node_id + 1, lo + n - node_id - 1, height - 1);
+            node_id
+        };
+        self.update_size_for(node_id);
+        Some(node_id)
+    }
+
+    pub fn delete_min(&mut self) {
+        self.pop_first();
+    }
+
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        let root_id = self.root?;
+        let left_is_red = self.is_red(self.nodes[root_id].left);
+        let right_is_red = self.is_red(self.nodes[root_id].right);
+        if !left_is_red && !right_is_red {
+            self.nodes[root_id].color = Color::Red;
+        }
+
+        // rotations only relink nodes, so the minimum stays in its slot
+        let min_id = self.rmin(root_id);
+        self.root = self.rdelete_min(root_id);
+        if let Some(root_id) = self.root {
+            self.nodes[root_id].color = Color::Black;
+        }
+
+        let node = self.free_node(min_id);
+        Some((node.key, node.value))
+    }
+
+    fn rdelete_min(&mut self, mut node_id: NodeId) -> Option<NodeId> {
+        self.nodes[node_id].left?;
+
         if !self.is_red(self.nodes[node_id].left) {
             let left_id = self.nodes[node_id].left.unwrap();
             if !self.is_red(self.nodes[left_id].left) {
@@ -212,27 +661,83 @@ impl<K, V> RedBlackTree<K, V>
         Some(self.balance(node_id))
     }
 
-    pub fn delete(&mut self, key: &K) {
-        if !self.contains(key) {
-            return;
+    pub fn delete_max(&mut self) {
+        self.pop_last();
+    }
+
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        let root_id = self.root?;
+        let left_is_red = self.is_red(self.nodes[root_id].left);
+        let right_is_red = self.is_red(self.nodes[root_id].right);
+        if !left_is_red && !right_is_red {
+            self.nodes[root_id].color = Color::Red;
         }
 
+        let max_id = self.rmax(root_id);
+        self.root = self.rdelete_max(root_id);
         if let Some(root_id) = self.root {
-            let left_is_red = self.is_red(self.nodes[root_id].left);
-            let right_is_red = self.is_red(self.nodes[root_id].right);
-            if !left_is_red && !right_is_red {
-                self.nodes[root_id].color = Color::Red;
-            }
+            self.nodes[root_id].color = Color::Black;
+        }
+
+        let node = self.free_node(max_id);
+        Some((node.key, node.value))
+    }
+
+    fn rdelete_max(&mut self, mut node_id: NodeId) -> Option<NodeId> {
+        if self.is_red(self.nodes[node_id].left) {
+            node_id = self.rotate_right(node_id);
+        }
 
-            self.root = self.rdelete(root_id, key);
-            if let Some(root_id) = self.root {
-                self.nodes[root_id].color = Color::Black;
+        self.nodes[node_id].right?;
+
+        if !self.is_red(self.nodes[node_id].right) {
+            let right_id = self.nodes[node_id].right.unwrap();
+            if !self.is_red(self.nodes[right_id].left) {
+                node_id = self.move_red_right(node_id);
             }
         }
+
+        // Note: right can't be None, even with move_red_right operation
+        let right_id = self.nodes[node_id].right.unwrap();
+        self.nodes[node_id].right = self.rdelete_max(right_id);
+
+        Some(self.balance(node_id))
+    }
+
+    pub fn delete(&mut self, key: &K) {
+        self.remove(key);
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        self.remove_entry(key).map(|(_, value)| value)
     }
 
-    fn rdelete(&mut self, mut node_id: NodeId, key: &K) -> Option<NodeId> {
-        if *key < self.nodes[node_id].key {
+    pub fn remove_entry(&mut self, key: &K) -> Option<(K, V)> {
+        let node_id = self.find(key)?;
+        Some(self.remove_node(node_id))
+    }
+
+    // Removes the node, which must be in the tree, walking down to it by
+    // its key
+    pub(crate) fn remove_node(&mut self, node_id: NodeId) -> (K, V) {
+        let root_id = self.root.unwrap();
+        let left_is_red = self.is_red(self.nodes[root_id].left);
+        let right_is_red = self.is_red(self.nodes[root_id].right);
+        if !left_is_red && !right_is_red {
+            self.nodes[root_id].color = Color::Red;
+        }
+
+        self.root = self.rdelete(root_id, node_id);
+        if let Some(root_id) = self.root {
+            self.nodes[root_id].color = Color::Black;
+        }
+
+        let node = self.free_node(node_id);
+        (node.key, node.value)
+    }
+
+    fn rdelete(&mut self, mut node_id: NodeId, target: NodeId) -> Option<NodeId> {
+        if self.nodes[target].key < self.nodes[node_id].key {
             if !self.is_red(self.nodes[node_id].left) {
                 let left_id = self.nodes[node_id].left.unwrap();
                 if !self.is_red(self.nodes[left_id].left) {
@@ -240,12 +745,12 @@ impl<K, V> RedBlackTree<K, V>
                 }
             }
             let left_id = self.nodes[node_id].left.unwrap();
-            self.nodes[node_id].left = self.rdelete(left_id, key);
+            self.nodes[node_id].left = self.rdelete(left_id, target);
         } else {
             if self.is_red(self.nodes[node_id].left) {
                 node_id = self.rotate_right(node_id);
             }
-            if *key == self.nodes[node_id].key && self.nodes[node_id].right.is_none() {
+            if node_id == target && self.nodes[node_id].right.is_none() {
                 return None;
             }
             
@@ -255,15 +760,20 @@ impl<K, V> RedBlackTree<K, V>
                     node_id = self.move_red_right(node_id);
                 }
             }
-            if *key == self.nodes[node_id].key {
+            if node_id == target {
+                // The successor is unlinked from the right subtree and takes
+                // the place of the deleted node, which keeps its own slot so
+                // that `remove_node` can free it.
                 let right_id = self.nodes[node_id].right.unwrap();
                 let x_id = self.rmin(right_id);
-                self.nodes[node_id].key = self.nodes[x_id].key.clone();
-                self.nodes[node_id].value = self.nodes[x_id].value.clone();
-                self.nodes[node_id].right = self.rdelete_min(right_id);
+                let right = self.rdelete_min(right_id);
+                self.nodes[x_id].left = self.nodes[node_id].left;
+                self.nodes[x_id].right = right;
+                self.nodes[x_id].color = self.nodes[node_id].color;
+                node_id = x_id;
             } else {
                 let right_id = self.nodes[node_id].right.unwrap();
-                self.nodes[node_id].right = self.rdelete(right_id, key);
+                self.nodes[node_id].right = self.rdelete(right_id, target);
             }
         }
         Some(self.balance(node_id))
@@ -405,22 +915,215 @@ impl<K, V> RedBlackTree<K, V>
         }
     }
 
+    pub fn floor(&self, key: &K) -> Option<&K> {
+        let mut floor = None;
+        let mut x = self.root;
+        while let Some(node_id) = x {
+            let node = &self.nodes[node_id];
+            match key.cmp(&node.key) {
+                Ordering::Less => x = node.left,
+                Ordering::Greater => {
+                    floor = Some(&node.key);
+                    x = node.right;
+                }
+                Ordering::Equal => return Some(&node.key),
+            }
+        }
+        floor
+    }
+
+    pub fn ceiling(&self, key: &K) -> Option<&K> {
+        let mut ceiling = None;
+        let mut x = self.root;
+        while let Some(node_id) = x {
+            let node = &self.nodes[node_id];
+            match key.cmp(&node.key) {
+                Ordering::Less => {
+                    ceiling = Some(&node.key);
+                    x = node.left;
+                }
+                Ordering::Greater => x = node.right,
+                Ordering::Equal => return Some(&node.key),
+            }
+        }
+        ceiling
+    }
+
+    // Number of keys smaller than key
+    pub fn rank(&self, key: &K) -> usize {
+        let mut rank = 0;
+        let mut x = self.root;
+        while let Some(node_id) = x {
+            let node = &self.nodes[node_id];
+            match key.cmp(&node.key) {
+                Ordering::Less => x = node.left,
+                Ordering::Greater => {
+                    rank += 1 + self.size_of(node.left);
+                    x = node.right;
+                }
+                Ordering::Equal => return rank + self.size_of(node.left),
+            }
+        }
+        rank
+    }
+
+    // Key with the given rank, counting from 0
+    pub fn select(&self, mut rank: usize) -> Option<&K> {
+        let mut x = self.root;
+        while let Some(node_id) = x {
+            let node = &self.nodes[node_id];
+            let left_size = self.size_of(node.left);
+            match rank.cmp(&left_size) {
+                Ordering::Less => x = node.left,
+                Ordering::Greater => {
+                    rank -= left_size + 1;
+                    x = node.right;
+                }
+                Ordering::Equal => return Some(&node.key),
+            }
+        }
+        None
+    }
+
+    // Number of keys between lo and hi, both inclusive
+    pub fn size_range(&self, lo: &K, hi: &K) -> usize {
+        if lo > hi {
+            return 0;
+        }
+
+        if self.contains(hi) {
+            self.rank(hi) - self.rank(lo) + 1
+        } else {
+            self.rank(hi) - self.rank(lo)
+        }
+    }
+
     // Iterator
-    pub fn iter(&self) -> TreeIter<K, V> {
-        let mut iter = TreeIter {
-            stack: Vec::new(),
-            tree: self,
-        };
-        iter.push_left_edge(self.root);
-        iter
+    pub fn iter(&self) -> TreeIter<'_, K, V> {
+        TreeIter { inner: self.range(..) }
     }
 
-    pub fn keys(&self) -> Keys<K, V> {
+    pub fn keys(&self) -> Keys<'_, K, V> {
         Keys { inner: self.iter() }
     }
+
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values { inner: self.iter() }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        let mut front = Vec::new();
+        let mut x = self.root;
+        while let Some(node_id) = x {
+            front.push(node_id);
+            x = self.nodes[node_id].left;
+        }
+
+        let mut back = Vec::new();
+        let mut x = self.root;
+        while let Some(node_id) = x {
+            back.push(node_id);
+            x = self.nodes[node_id].right;
+        }
+
+        IterMut {
+            front,
+            back,
+            remaining: self.size(),
+            nodes: self.nodes.as_mut_ptr(),
+            marker: PhantomData,
+        }
+    }
+
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut { inner: self.iter_mut() }
+    }
+
+    fn inorder_ids(&self) -> Vec<NodeId> {
+        let mut ids = Vec::with_capacity(self.nodes.len());
+        let mut stack = Vec::new();
+        let mut x = self.root;
+        loop {
+            while let Some(node_id) = x {
+                stack.push(node_id);
+                x = self.nodes[node_id].left;
+            }
+            match stack.pop() {
+                Some(node_id) => {
+                    ids.push(node_id);
+                    x = self.nodes[node_id].right;
+                }
+                None => return ids,
+            }
+        }
+    }
+
+    // Entries with keys in the given range, found in O(log n) time
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> Range<'_, K, V> {
+        let after_start = |key: &K| match range.start_bound() {
+            Bound::Included(start) => key >= start,
+            Bound::Excluded(start) => key > start,
+            Bound::Unbounded => true,
+        };
+        let before_end = |key: &K| match range.end_bound() {
+            Bound::Included(end) => key <= end,
+            Bound::Excluded(end) => key < end,
+            Bound::Unbounded => true,
+        };
+
+        let mut front = Vec::new();
+        let mut x = self.root;
+        while let Some(node_id) = x {
+            if after_start(&self.nodes[node_id].key) {
+                front.push(node_id);
+                x = self.nodes[node_id].left;
+            } else {
+                x = self.nodes[node_id].right;
+            }
+        }
+
+        let mut back = Vec::new();
+        let mut x = self.root;
+        while let Some(node_id) = x {
+            if before_end(&self.nodes[node_id].key) {
+                back.push(node_id);
+                x = self.nodes[node_id].right;
+            } else {
+                x = self.nodes[node_id].left;
+            }
+        }
+
+        let up_to_end = self.count_before(|key| !before_end(key));
+        let before_start = self.count_before(after_start);
+        Range {
+            front,
+            back,
+            remaining: up_to_end.saturating_sub(before_start),
+            tree: self,
+        }
+    }
+
+    // Walks down going left at the keys for which goes_left is true and
+    // counts the keys left behind on the left
+    fn count_before<F: Fn(&K) -> bool>(&self, goes_left: F) -> usize {
+        let mut count = 0;
+        let mut x = self.root;
+        while let Some(node_id) = x {
+            let node = &self.nodes[node_id];
+            if goes_left(&node.key) {
+                x = node.left;
+            } else {
+                count += 1 + self.size_of(node.left);
+                x = node.right;
+            }
+        }
+        count
+    }
 }
 
 #[cfg(test)]
+// the original tests compare bools with `assert_eq!`
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
 
@@ -633,6 +1336,132 @@ mod tests {
         assert_eq!(right.color, Color::Black);
     }
 
+    #[test]
+    fn delete_max() {
+        let mut tree: RedBlackTree<String, usize> = RedBlackTree::new();
+        tree.delete_max();
+
+        populate_tree(&mut tree);
+        tree.delete_max();
+        assert_eq!(tree.max(), Some(&"R".to_string()));
+        tree.delete_max();
+        tree.delete_max();
+        assert_eq!(tree.max(), Some(&"E".to_string()));
+        assert_eq!(tree.size(), 3);
+        let root_id = tree.root.unwrap();
+        assert_eq!(tree.nodes[root_id].color, Color::Black);
+    }
+
+    #[test]
+    fn pop_first_and_last() {
+        let mut tree = RedBlackTree::new();
+        assert_eq!(tree.pop_first(), None);
+        assert_eq!(tree.pop_last(), None);
+
+        populate_tree(&mut tree);
+        assert_eq!(tree.pop_first(), Some(("A".to_string(), 8)));
+        assert_eq!(tree.pop_last(), Some(("S".to_string(), 0)));
+        assert_eq!(tree.pop_first(), Some(("C".to_string(), 4)));
+        assert_eq!(tree.pop_last(), Some(("R".to_string(), 3)));
+        assert_eq!(tree.pop_last(), Some(("H".to_string(), 5)));
+        assert_eq!(tree.pop_first(), Some(("E".to_string(), 12)));
+        assert_eq!(tree.pop_first(), None);
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn remove() {
+        let mut tree = RedBlackTree::new();
+        populate_tree(&mut tree);
+
+        assert_eq!(tree.remove(&"E".to_string()), Some(12));
+        assert_eq!(tree.remove(&"E".to_string()), None);
+        assert_eq!(tree.remove(&"Z".to_string()), None);
+        assert_eq!(tree.remove(&"S".to_string()), Some(0));
+        assert_eq!(tree.size(), 4);
+        assert_eq!(tree.get(&"H".to_string()), Some(&5));
+    }
+
+    // Neither keys nor values need to be Clone.
+    #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+    struct Key(usize);
+
+    #[derive(Debug, PartialEq)]
+    struct Value(String);
+
+    #[test]
+    fn owned_entries() {
+        let mut tree = RedBlackTree::new();
+        for i in 0..10 {
+            tree.put(Key(i), Value(i.to_string()));
+        }
+
+        assert_eq!(tree.pop_first(), Some((Key(0), Value("0".to_string()))));
+        assert_eq!(tree.pop_last(), Some((Key(9), Value("9".to_string()))));
+        assert_eq!(tree.remove(&Key(5)), Some(Value("5".to_string())));
+        tree.delete(&Key(3));
+        tree.delete_min();
+        tree.delete_max();
+        let keys: Vec<&Key> = tree.keys().collect();
+        assert_eq!(keys, vec![&Key(2), &Key(4), &Key(6), &Key(7)]);
+    }
+
+    #[test]
+    fn non_clone_values() {
+        use std::fs::File;
+
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml");
+        let mut tree = RedBlackTree::new();
+        for key in 0..20 {
+            tree.put(key, File::open(path).unwrap());
+        }
+
+        // deleting a node with two children relinks its successor instead
+        // of copying it
+        let root_id = tree.root.unwrap();
+        let root_key = tree.nodes[root_id].key;
+        assert!(tree.remove(&root_key).is_some());
+        assert!(!tree.contains(&root_key));
+        tree.delete(&10);
+        tree.delete_min();
+        tree.delete_max();
+        assert!(tree.pop_first().is_some());
+        assert_eq!(tree.size(), 15);
+        assert_eq!(tree.nodes.len(), 15);
+        assert!(tree.get(&5).unwrap().metadata().is_ok());
+    }
+
+    #[test]
+    fn delete_frees_slots() {
+        let mut tree = RedBlackTree::new();
+
+        for round in 0..100 {
+            for key in 0..1000 {
+                tree.put(key, round);
+            }
+            for key in (0..1000).filter(|key| key % 3 != 0) {
+                tree.delete(&key);
+            }
+            tree.delete_min();
+
+            // keys 3, 6, ..., 999 are left and no slot is wasted
+            assert_eq!(tree.size(), 333);
+            assert_eq!(tree.nodes.len(), tree.size());
+        }
+
+        let keys: Vec<usize> = tree.keys().cloned().collect();
+        assert_eq!(keys, (1..334).map(|key| key * 3).collect::<Vec<_>>());
+        assert!(tree.iter().all(|(_, value)| *value == 99));
+
+        while !tree.is_empty() {
+            let max = *tree.max().unwrap();
+            tree.delete(&max);
+            tree.delete_min();
+            assert_eq!(tree.nodes.len(), tree.size());
+        }
+        assert!(tree.nodes.is_empty());
+    }
+
     #[test]
     fn tree_size() {
         let mut tree = RedBlackTree::new();
@@ -668,6 +1497,71 @@ mod tests {
         assert_eq!(tree.max(), Some(&"S".to_string()));
     }
 
+    #[test]
+    fn floor_and_ceiling() {
+        let mut tree = RedBlackTree::new();
+        assert_eq!(tree.floor(&"E".to_string()), None);
+        assert_eq!(tree.ceiling(&"E".to_string()), None);
+
+        populate_tree(&mut tree);
+
+        assert_eq!(tree.floor(&"E".to_string()), Some(&"E".to_string()));
+        assert_eq!(tree.floor(&"G".to_string()), Some(&"E".to_string()));
+        assert_eq!(tree.floor(&"Z".to_string()), Some(&"S".to_string()));
+        assert_eq!(tree.floor(&"0".to_string()), None);
+
+        assert_eq!(tree.ceiling(&"E".to_string()), Some(&"E".to_string()));
+        assert_eq!(tree.ceiling(&"G".to_string()), Some(&"H".to_string()));
+        assert_eq!(tree.ceiling(&"0".to_string()), Some(&"A".to_string()));
+        assert_eq!(tree.ceiling(&"Z".to_string()), None);
+    }
+
+    #[test]
+    fn rank_and_select() {
+        let mut tree = RedBlackTree::new();
+        assert_eq!(tree.rank(&"E".to_string()), 0);
+        assert_eq!(tree.select(0), None);
+
+        populate_tree(&mut tree);
+
+        let keys = ["A", "C", "E", "H", "R", "S"];
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(tree.rank(&key.to_string()), i);
+            assert_eq!(tree.select(i), Some(&key.to_string()));
+        }
+        assert_eq!(tree.select(6), None);
+        assert_eq!(tree.rank(&"0".to_string()), 0);
+        assert_eq!(tree.rank(&"D".to_string()), 2);
+        assert_eq!(tree.rank(&"Z".to_string()), 6);
+
+        let mut tree = RedBlackTree::new();
+        for key in 0..1000 {
+            tree.put(key * 2, ());
+        }
+        for rank in 0..1000 {
+            assert_eq!(tree.select(rank), Some(&(rank * 2)));
+            assert_eq!(tree.rank(&(rank * 2)), rank);
+            assert_eq!(tree.rank(&(rank * 2 + 1)), rank + 1);
+        }
+    }
+
+    #[test]
+    fn size_range() {
+        let mut tree = RedBlackTree::new();
+        assert_eq!(tree.size_range(&"A".to_string(), &"Z".to_string()), 0);
+
+        populate_tree(&mut tree);
+
+        let size_range = |lo: &str, hi: &str| tree.size_range(&lo.to_string(), &hi.to_string());
+        assert_eq!(size_range("A", "S"), 6);
+        assert_eq!(size_range("0", "Z"), 6);
+        assert_eq!(size_range("C", "H"), 3);
+        assert_eq!(size_range("D", "I"), 2);
+        assert_eq!(size_range("E", "E"), 1);
+        assert_eq!(size_range("F", "G"), 0);
+        assert_eq!(size_range("S", "A"), 0);
+    }
+
     #[test]
     fn iterator() {
         let mut tree = RedBlackTree::new();
@@ -683,6 +1577,273 @@ mod tests {
         assert_eq!(tree_iter.next(), None);
     }
 
+    #[test]
+    fn iterator_double_ended() {
+        let mut tree = RedBlackTree::new();
+        populate_tree(&mut tree);
+
+        let mut tree_iter = tree.iter();
+        assert_eq!(tree_iter.size_hint(), (6, Some(6)));
+        assert_eq!(tree_iter.next_back(), Some((&"S".to_string(), &0)));
+        assert_eq!(tree_iter.next(), Some((&"A".to_string(), &8)));
+        assert_eq!(tree_iter.size_hint(), (4, Some(4)));
+
+        let keys: Vec<&str> = tree.iter().rev().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(keys, vec!["S", "R", "H", "E", "C", "A"]);
+        let keys: Vec<&str> = tree.keys().rev().map(|k| k.as_str()).collect();
+        assert_eq!(keys, vec!["S", "R", "H", "E", "C", "A"]);
+        let values: Vec<&usize> = tree.values().rev().collect();
+        assert_eq!(values, vec![&0, &3, &5, &12, &4, &8]);
+        assert_eq!(tree.keys().size_hint(), (6, Some(6)));
+        assert_eq!(tree.values().size_hint(), (6, Some(6)));
+    }
+
+    #[test]
+    fn range() {
+        let mut tree = RedBlackTree::new();
+        for key in 0..20 {
+            tree.put(key * 2, key);
+        }
+        let keys = |range: Range<usize, usize>| range.map(|(k, _)| *k).collect::<Vec<_>>();
+
+        assert_eq!(keys(tree.range(5..11)), vec![6, 8, 10]);
+        assert_eq!(keys(tree.range(6..=10)), vec![6, 8, 10]);
+        assert_eq!(keys(tree.range(..3)), vec![0, 2]);
+        assert_eq!(keys(tree.range(35..)), vec![36, 38]);
+        assert_eq!(keys(tree.range(..)).len(), 20);
+        assert_eq!(keys(tree.range((Bound::Excluded(6), Bound::Included(10)))), vec![8, 10]);
+        assert_eq!(keys(tree.range(7..8)), vec![]);
+        assert_eq!(keys(tree.range(40..)), vec![]);
+        assert_eq!(keys(tree.range((Bound::Excluded(6), Bound::Excluded(6)))), vec![]);
+        assert_eq!(tree.range(5..11).next(), Some((&6, &3)));
+        assert_eq!(tree.range(5..11).size_hint(), (3, Some(3)));
+
+        let empty: RedBlackTree<usize, usize> = RedBlackTree::new();
+        assert_eq!(empty.range(..).next(), None);
+    }
+
+    #[test]
+    fn range_double_ended() {
+        let mut tree = RedBlackTree::new();
+        for key in 0..100 {
+            tree.put(key, key * 10);
+        }
+
+        let mut range = tree.range(10..20);
+        assert_eq!(range.next_back(), Some((&19, &190)));
+        assert_eq!(range.next(), Some((&10, &100)));
+        assert_eq!(range.next_back(), Some((&18, &180)));
+        let rest: Vec<_> = range.map(|(k, _)| *k).collect();
+        assert_eq!(rest, (11..18).collect::<Vec<_>>());
+
+        // from both ends, the iterators meet without crossing
+        for lo in 0..20 {
+            for hi in lo..20 {
+                let mut range = tree.range(lo * 5..hi * 5);
+                let mut keys = Vec::new();
+                let mut back = Vec::new();
+                while let Some((k, _)) = range.next() {
+                    keys.push(*k);
+                    match range.next_back() {
+                        Some((k, _)) => back.push(*k),
+                        None => break,
+                    }
+                }
+                keys.extend(back.into_iter().rev());
+                assert_eq!(keys, (lo * 5..hi * 5).collect::<Vec<_>>());
+            }
+        }
+
+        let keys: Vec<_> = tree.range(90..).rev().map(|(k, _)| *k).collect();
+        assert_eq!(keys, (90..100).rev().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn values() {
+        let mut tree = RedBlackTree::new();
+        populate_tree(&mut tree);
+
+        let values: Vec<&usize> = tree.values().collect();
+        assert_eq!(values, vec![&8, &4, &12, &5, &3, &0]);
+
+        for value in tree.values_mut() {
+            *value += 100;
+        }
+        assert_eq!(tree.get(&"E".to_string()), Some(&112));
+
+        let mut values = tree.values_mut();
+        assert_eq!(values.size_hint(), (6, Some(6)));
+        *values.next_back().unwrap() = 0;
+        *values.next().unwrap() = 1;
+        assert_eq!(values.count(), 4);
+        assert_eq!(tree.get(&"S".to_string()), Some(&0));
+        assert_eq!(tree.get(&"A".to_string()), Some(&1));
+    }
+
+    #[test]
+    fn get_mut() {
+        let mut tree = RedBlackTree::new();
+        assert_eq!(tree.get_mut(&"E".to_string()), None);
+
+        populate_tree(&mut tree);
+        *tree.get_mut(&"E".to_string()).unwrap() += 1;
+        assert_eq!(tree.get(&"E".to_string()), Some(&13));
+        assert_eq!(tree.get_mut(&"Z".to_string()), None);
+    }
+
+    #[test]
+    fn iter_mut() {
+        let mut tree = RedBlackTree::new();
+        populate_tree(&mut tree);
+
+        for (key, value) in tree.iter_mut() {
+            if key.as_str() < "F" {
+                *value *= 10;
+            }
+        }
+        let entries: Vec<_> = tree.iter().map(|(k, v)| (k.as_str(), *v)).collect();
+        assert_eq!(entries, vec![("A", 80), ("C", 40), ("E", 120), ("H", 5), ("R", 3), ("S", 0)]);
+
+        let mut iter = tree.iter_mut();
+        assert_eq!(iter.next_back(), Some((&"S".to_string(), &mut 0)));
+        assert_eq!(iter.next(), Some((&"A".to_string(), &mut 80)));
+        assert_eq!(iter.count(), 4);
+
+        // the values lent out from both ends are all usable at once
+        let mut numbers: RedBlackTree<usize, usize> = (0..100).map(|key| (key, key)).collect();
+        let mut iter = numbers.iter_mut();
+        let mut values = Vec::new();
+        while let Some((_, value)) = iter.next() {
+            values.push(value);
+            values.extend(iter.next_back().map(|(_, value)| value));
+        }
+        assert_eq!(values.len(), 100);
+        for value in values {
+            *value += 1;
+        }
+        assert!(numbers.values().cloned().eq(1..101));
+
+        // and the iterator can be used on another thread
+        let iter = numbers.iter_mut();
+        std::thread::scope(|scope| {
+            scope.spawn(move || iter.for_each(|(_, value)| *value -= 1));
+        });
+        assert!(numbers.values().cloned().eq(0..100));
+    }
+
+    #[test]
+    fn collect_and_extend() {
+        let mut tree: RedBlackTree<usize, char> = "hello".chars().enumerate().collect();
+        assert_eq!(tree.size(), 5);
+        assert_eq!(tree[&1], 'e');
+
+        tree.extend(vec![(1, 'a'), (5, '!')]);
+        assert_eq!(tree.values().collect::<String>(), "hallo!");
+
+        let tree = RedBlackTree::from([(3, "c"), (1, "a"), (2, "b")]);
+        assert_eq!(tree.keys().cloned().collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(RedBlackTree::<usize, usize>::default(), RedBlackTree::new());
+    }
+
+    #[test]
+    fn into_iter() {
+        let mut tree: RedBlackTree<String, usize> = RedBlackTree::new();
+        populate_tree(&mut tree);
+
+        let mut keys = Vec::new();
+        for (key, _) in &tree {
+            keys.push(key.clone());
+        }
+        assert_eq!(keys, vec!["A", "C", "E", "H", "R", "S"]);
+
+        for (_, value) in &mut tree {
+            *value += 1;
+        }
+
+        let entries: Vec<(String, usize)> = tree.clone().into_iter().collect();
+        assert_eq!(entries[0], ("A".to_string(), 9));
+        assert_eq!(entries.len(), 6);
+
+        let mut iter = tree.into_iter();
+        assert_eq!(iter.next_back(), Some(("S".to_string(), 1)));
+        assert_eq!(iter.next(), Some(("A".to_string(), 9)));
+        assert_eq!(iter.size_hint(), (4, Some(4)));
+        assert_eq!(iter.rev().map(|(k, _)| k).collect::<Vec<_>>(), vec!["R", "H", "E", "C"]);
+
+        // the arena is out of key order after deletes
+        let mut numbers: RedBlackTree<usize, usize> = (0..50).rev().map(|key| (key, key * 2)).collect();
+        for key in (0..50).step_by(3) {
+            numbers.delete(&key);
+        }
+        let expected: Vec<_> = (0..50).filter(|key| key % 3 != 0).map(|key| (key, key * 2)).collect();
+        assert_eq!(numbers.into_iter().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn debug() {
+        let tree: RedBlackTree<usize, &str> = RedBlackTree::new();
+        assert_eq!(format!("{:?}", tree), "{}");
+
+        let tree = RedBlackTree::from([(3, "c"), (1, "a"), (2, "b")]);
+        assert_eq!(format!("{:?}", tree), r#"{1: "a", 2: "b", 3: "c"}"#);
+    }
+
+    #[test]
+    fn clone_and_eq() {
+        let mut tree = RedBlackTree::new();
+        populate_tree(&mut tree);
+
+        let mut copy = tree.clone();
+        assert_eq!(copy, tree);
+        copy.put("Z".to_string(), 1);
+        assert_ne!(copy, tree);
+        copy.delete(&"Z".to_string());
+        assert_eq!(copy, tree);
+        *copy.get_mut(&"A".to_string()).unwrap() = 0;
+        assert_ne!(copy, tree);
+        assert_eq!(tree.get(&"A".to_string()), Some(&8));
+
+        // the same entries inserted in another order give an equal tree
+        let mut entries: Vec<(String, usize)> = tree.iter().map(|(k, v)| (k.clone(), *v)).collect();
+        entries.reverse();
+        let other: RedBlackTree<String, usize> = entries.into_iter().collect();
+        assert_eq!(other, tree);
+    }
+
+    #[test]
+    #[should_panic(expected = "no entry found for key")]
+    fn index_missing_key() {
+        let tree: RedBlackTree<usize, usize> = RedBlackTree::new();
+        let _ = tree[&1];
+    }
+
+    #[test]
+    fn from_sorted_iter() {
+        for n in 0..300 {
+            let tree = RedBlackTree::from_sorted_iter((0..n).map(|key| (key, key * 2)));
+            assert_eq!(tree.check_invariants(), Ok(()));
+            assert_eq!(tree.size(), n);
+            assert!(tree.iter().map(|(k, v)| (*k, *v)).eq((0..n).map(|key| (key, key * 2))));
+        }
+
+        let mut tree = RedBlackTree::from_sorted_iter(vec![(1, "a"), (2, "b"), (2, "c"), (5, "d")]);
+        assert_eq!(tree.size(), 3);
+        assert_eq!(tree.get(&2), Some(&"c"));
+
+        // the tree stays valid as it changes
+        for key in 0..10 {
+            tree.put(key, "e");
+            tree.delete(&(key / 2));
+            assert_eq!(tree.check_invariants(), Ok(()));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "keys must be in increasing order")]
+    fn from_unsorted_iter() {
+        RedBlackTree::from_sorted_iter(vec![(1, ()), (3, ()), (2, ())]);
+    }
+
     #[test]
     fn keys() {
         let mut tree = RedBlackTree::new();
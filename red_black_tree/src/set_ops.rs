@@ -0,0 +1,451 @@
+use std::cmp::Ordering;
+use std::mem;
+
+use crate::{Color, Node, NodeId, RedBlackTree};
+
+// The keys a set operation keeps: those only in the first tree, those only
+// in the second, and those in both.
+#[derive(Clone, Copy)]
+struct Keep {
+    first: bool,
+    second: bool,
+    both: bool,
+}
+
+impl<K: Ord, V> RedBlackTree<K, V> {
+    // Set operations, in O(m log n) time for trees of sizes m <= n
+
+    // Entries of both trees, keeping the value of self for keys in both
+    pub fn union(self, other: Self) -> Self {
+        self.union_with(other, |_, _, _| {})
+    }
+
+    // Entries of both trees, combining the values of keys in both with f
+    pub fn union_with<F: FnMut(&K, &mut V, V)>(self, other: Self, f: F) -> Self {
+        self.merge(other, Keep { first: true, second: true, both: true }, f)
+    }
+
+    // Entries of self whose keys are in other
+    pub fn intersection(self, other: Self) -> Self {
+        self.intersection_with(other, |_, _, _| {})
+    }
+
+    // Keys in both trees, with their values combined with f
+    pub fn intersection_with<F: FnMut(&K, &mut V, V)>(self, other: Self, f: F) -> Self {
+        self.merge(other, Keep { first: false, second: false, both: true }, f)
+    }
+
+    // Entries of self whose keys are not in other
+    pub fn difference(self, other: Self) -> Self {
+        self.merge(other, Keep { first: true, second: false, both: false }, |_, _, _| {})
+    }
+
+    // Entries whose keys are in one tree but not in both
+    pub fn symmetric_difference(self, other: Self) -> Self {
+        self.merge(other, Keep { first: true, second: true, both: false }, |_, _, _| {})
+    }
+
+    fn merge<F: FnMut(&K, &mut V, V)>(self, other: Self, keep: Keep, mut f: F) -> Self {
+        if self.size() >= other.size() {
+            return self.merge_smaller(other, keep, f);
+        }
+
+        // Merge the other way around, swapping the values back before
+        // combining them.
+        let keep = Keep { first: keep.second, second: keep.first, both: keep.both };
+        other.merge_smaller(self, keep, |key, value, mut self_value| {
+            mem::swap(value, &mut self_value);
+            f(key, value, self_value);
+        })
+    }
+
+    // Splits this tree by every key of the smaller tree, from its root down,
+    // and joins the pieces back with the entries to keep. The nodes of the
+    // smaller tree that are kept move into this arena.
+    fn merge_smaller<F: FnMut(&K, &mut V, V)>(mut self, other: Self, keep: Keep, mut f: F) -> Self {
+        let mut other_nodes: Vec<_> = other.nodes.into_iter().map(Some).collect();
+        let mut dropped = Vec::new();
+        let root = self.root.take();
+        self.root = self.merge_node(root, other.root, &mut other_nodes, keep, &mut f, &mut dropped);
+        if let Some(root_id) = self.root {
+            self.nodes[root_id].color = Color::Black;
+        }
+
+        self.reclaim(dropped);
+        self
+    }
+
+    fn merge_node<F: FnMut(&K, &mut V, V)>(
+        &mut self,
+        node: Option<NodeId>,
+        other_node: Option<NodeId>,
+        other_nodes: &mut [Option<Node<K, V>>],
+        keep: Keep,
+        f: &mut F,
+        dropped: &mut Vec<NodeId>,
+    ) -> Option<NodeId> {
+        let other_id = match other_node {
+            Some(other_id) => other_id,
+            None if keep.first => return node,
+            None => {
+                self.collect_ids(node, dropped);
+                return None;
+            }
+        };
+        if node.is_none() && !keep.second {
+            return None;
+        }
+
+        let other = other_nodes[other_id].take().unwrap();
+        let (left, found, right) = self.split(node, &other.key);
+        let left = self.merge_node(left, other.left, other_nodes, keep, f, dropped);
+        let right = self.merge_node(right, other.right, other_nodes, keep, f, dropped);
+
+        let middle = match found {
+            Some(node_id) if keep.both => {
+                f(&other.key, &mut self.nodes[node_id].value, other.value);
+                Some(node_id)
+            }
+            Some(node_id) => {
+                dropped.push(node_id);
+                None
+            }
+            None if keep.second => Some(self.new_node(other.key, other.value, Color::Black)),
+            None => None,
+        };
+        match middle {
+            Some(node_id) => Some(self.join(left, node_id, right)),
+            None => self.join2(left, right),
+        }
+    }
+
+    // Splits the subtree into the nodes with keys smaller than the given
+    // key, the node with the key, if there is one, and the nodes with larger
+    // keys. The pieces may have red roots.
+    fn split(&mut self, node: Option<NodeId>, key: &K) -> (Option<NodeId>, Option<NodeId>, Option<NodeId>) {
+        let node_id = match node {
+            Some(node_id) => node_id,
+            None => return (None, None, None),
+        };
+
+        let left = self.nodes[node_id].left;
+        let right = self.nodes[node_id].right;
+        match key.cmp(&self.nodes[node_id].key) {
+            Ordering::Less => {
+                let (smaller, found, larger) = self.split(left, key);
+                (smaller, found, Some(self.join(larger, node_id, right)))
+            }
+            Ordering::Greater => {
+                let (smaller, found, larger) = self.split(right, key);
+                (Some(self.join(left, node_id, smaller)), found, larger)
+            }
+            Ordering::Equal => (left, Some(node_id), right),
+        }
+    }
+
+    // Joins two subtrees and a node whose key is between theirs into a
+    // subtree with a black root. The node is linked in where the black
+    // heights meet, in time proportional to the height of the taller one.
+    fn join(&mut self, left: Option<NodeId>, node_id: NodeId, right: Option<NodeId>) -> NodeId {
+        self.set_black(left);
+        self.set_black(right);
+        let left_height = self.black_height(left);
+        let right_height = self.black_height(right);
+
+        let root_id = match left_height.cmp(&right_height) {
+            Ordering::Equal => {
+                self.nodes[node_id].left = left;
+                self.nodes[node_id].right = right;
+                self.update_size_for(node_id);
+                node_id
+            }
+            Ordering::Greater => {
+                self.join_right(left.unwrap(), left_height, node_id, right, right_height)
+            }
+            Ordering::Less => {
+                self.join_left(left, left_height, node_id, right.unwrap(), right_height)
+            }
+        };
+        self.nodes[root_id].color = Color::Black;
+        root_id
+    }
+
+    // Walks down the right spine of the taller left subtree, whose nodes
+    // are all black, and links in the node as a red right child, fixing the
+    // right-leaning link on the way up as in `rput`.
+    fn join_right(
+        &mut self,
+        node_id: NodeId,
+        height: usize,
+        middle_id: NodeId,
+        right: Option<NodeId>,
+        right_height: usize,
+    ) -> NodeId {
+        let child = self.nodes[node_id].right;
+        self.nodes[node_id].right = if height - 1 == right_height {
+            self.nodes[middle_id].left = child;
+            self.nodes[middle_id].right = right;
+            self.nodes[middle_id].color = Color::Red;
+            self.update_size_for(middle_id);
+            Some(middle_id)
+        } else {
+            Some(self.join_right(child.unwrap(), height - 1, middle_id, right, right_height))
+        };
+        self.balance(node_id)
+    }
+
+    // Walks down the left spine of the taller right subtree to the first
+    // black node with the black height of the left subtree, and links in
+    // the node as a red left child in its place.
+    fn join_left(
+        &mut self,
+        left: Option<NodeId>,
+        left_height: usize,
+        middle_id: NodeId,
+        node_id: NodeId,
+        height: usize,
+    ) -> NodeId {
+        let child = self.nodes[node_id].left;
+        let child_height = match self.nodes[node_id].color {
+            Color::Black => height - 1,
+            Color::Red => height,
+        };
+        self.nodes[node_id].left = if child_height == left_height && !self.is_red(child) {
+            self.nodes[middle_id].left = left;
+            self.nodes[middle_id].right = child;
+            self.nodes[middle_id].color = Color::Red;
+            self.update_size_for(middle_id);
+            Some(middle_id)
+        } else {
+            Some(self.join_left(left, left_height, middle_id, child.unwrap(), child_height))
+        };
+        self.balance(node_id)
+    }
+
+    // Joins two subtrees without a node between them, using the largest
+    // node of the left one.
+    fn join2(&mut self, left: Option<NodeId>, right: Option<NodeId>) -> Option<NodeId> {
+        let left_id = match left {
+            Some(left_id) => left_id,
+            None => return right,
+        };
+        if right.is_none() {
+            return left;
+        }
+
+        // as in `pop_last`, but without freeing the largest node
+        self.nodes[left_id].color = Color::Black;
+        let left_is_red = self.is_red(self.nodes[left_id].left);
+        let right_is_red = self.is_red(self.nodes[left_id].right);
+        if !left_is_red && !right_is_red {
+            self.nodes[left_id].color = Color::Red;
+        }
+        let max_id = self.rmax(left_id);
+        let left = self.rdelete_max(left_id);
+
+        Some(self.join(left, max_id, right))
+    }
+
+    fn set_black(&mut self, node: Option<NodeId>) {
+        if let Some(node_id) = node {
+            self.nodes[node_id].color = Color::Black;
+        }
+    }
+
+    // Number of black nodes on the paths from the node down to the leaves
+    fn black_height(&self, mut node: Option<NodeId>) -> usize {
+        let mut height = 0;
+        while let Some(node_id) = node {
+            if self.nodes[node_id].color == Color::Black {
+                height += 1;
+            }
+            node = self.nodes[node_id].left;
+        }
+        height
+    }
+
+    fn collect_ids(&self, node: Option<NodeId>, ids: &mut Vec<NodeId>) {
+        let mut stack: Vec<NodeId> = node.into_iter().collect();
+        while let Some(node_id) = stack.pop() {
+            ids.push(node_id);
+            stack.extend(self.nodes[node_id].left);
+            stack.extend(self.nodes[node_id].right);
+        }
+    }
+
+    // Removes the dropped nodes, which are no longer in the tree, from the
+    // arena.
+    fn reclaim(&mut self, mut dropped: Vec<NodeId>) {
+        let log_n = (usize::BITS - self.nodes.len().leading_zeros()) as usize;
+        if dropped.len() * log_n <= self.nodes.len() {
+            // Freeing a node moves the last node of the arena into its slot,
+            // and that node is still in the tree when the larger ids are
+            // freed first.
+            dropped.sort_unstable_by(|a, b| b.cmp(a));
+            for node_id in dropped {
+                self.free_node(node_id);
+            }
+            return;
+        }
+
+        // Too many to free one at a time, so the nodes that are left are
+        // moved to new slots in key order, in O(n) time.
+        let order = self.inorder_ids();
+        let mut new_ids = vec![0; self.nodes.len()];
+        for (new_id, &node_id) in order.iter().enumerate() {
+            new_ids[node_id] = new_id;
+        }
+        let mut nodes: Vec<_> = mem::take(&mut self.nodes).into_iter().map(Some).collect();
+        self.nodes = order.iter()
+            .map(|&node_id| {
+                let mut node = nodes[node_id].take().unwrap();
+                node.left = node.left.map(|id| new_ids[id]);
+                node.right = node.right.map(|id| new_ids[id]);
+                node
+            })
+            .collect();
+        self.root = self.root.map(|id| new_ids[id]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::XorShift;
+    use std::collections::BTreeMap;
+
+    fn tree_of(keys: &[usize]) -> RedBlackTree<usize, String> {
+        keys.iter().map(|&key| (key, key.to_string())).collect()
+    }
+
+    fn keys(tree: &RedBlackTree<usize, String>) -> Vec<usize> {
+        tree.keys().cloned().collect()
+    }
+
+    #[test]
+    fn union() {
+        let tree = tree_of(&[1, 3, 5, 7]).union_with(tree_of(&[2, 3, 4, 7, 9]), |key, value, other| {
+            assert_eq!(value, &other);
+            value.push_str(&format!("+{}", key));
+        });
+        assert_eq!(keys(&tree), vec![1, 2, 3, 4, 5, 7, 9]);
+        assert_eq!(tree.get(&3), Some(&"3+3".to_string()));
+        assert_eq!(tree.get(&4), Some(&"4".to_string()));
+        assert_eq!(tree.check_invariants(), Ok(()));
+
+        let mut left = tree_of(&[1, 2]);
+        left.put(2, "left".to_string());
+        let tree = left.union(tree_of(&(0..20).collect::<Vec<_>>()));
+        assert_eq!(tree.size(), 20);
+        assert_eq!(tree.get(&2), Some(&"left".to_string()));
+        assert_eq!(tree.check_invariants(), Ok(()));
+
+        let tree = tree_of(&[]).union(tree_of(&[4, 2]));
+        assert_eq!(keys(&tree), vec![2, 4]);
+    }
+
+    #[test]
+    fn intersection() {
+        let large = tree_of(&(0..100).collect::<Vec<_>>());
+        let mut small = tree_of(&[5, 50, 500]);
+        small.put(50, "small".to_string());
+
+        let tree = small.clone().intersection(large.clone());
+        assert_eq!(keys(&tree), vec![5, 50]);
+        assert_eq!(tree.get(&50), Some(&"small".to_string()));
+        assert_eq!(tree.check_invariants(), Ok(()));
+
+        // the values of `self` come first, even if it's the larger tree
+        let tree = large.intersection_with(small, |_, value, other| {
+            *value = format!("{},{}", value, other);
+        });
+        assert_eq!(keys(&tree), vec![5, 50]);
+        assert_eq!(tree.get(&50), Some(&"50,small".to_string()));
+        assert_eq!(tree.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn difference() {
+        let tree = tree_of(&[1, 2, 3, 4, 5]).difference(tree_of(&[0, 2, 4, 6]));
+        assert_eq!(keys(&tree), vec![1, 3, 5]);
+        assert_eq!(tree.check_invariants(), Ok(()));
+
+        let tree = tree_of(&[2, 40]).difference(tree_of(&(0..30).collect::<Vec<_>>()));
+        assert_eq!(keys(&tree), vec![40]);
+        assert_eq!(tree.check_invariants(), Ok(()));
+
+        let tree = tree_of(&[1, 2, 3, 4, 5]).symmetric_difference(tree_of(&[0, 2, 4, 6]));
+        assert_eq!(keys(&tree), vec![0, 1, 3, 5, 6]);
+        assert_eq!(tree.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn key_sets() {
+        let evens: RedBlackTree<usize, ()> = (0..50).step_by(2).map(|key| (key, ())).collect();
+        let threes: RedBlackTree<usize, ()> = (0..50).step_by(3).map(|key| (key, ())).collect();
+
+        let sixes = evens.clone().intersection(threes.clone());
+        assert!(sixes.keys().cloned().eq((0..50).step_by(6)));
+        let either = evens.clone().union(threes.clone());
+        assert!(either.keys().all(|key| key % 2 == 0 || key % 3 == 0));
+        assert_eq!(either.size(), 25 + 17 - 9);
+        let only_evens = evens.clone().difference(threes.clone());
+        assert!(only_evens.keys().all(|key| key % 2 == 0 && key % 3 != 0));
+        assert_eq!(evens.symmetric_difference(threes).size(), 25 + 17 - 2 * 9);
+    }
+
+    #[test]
+    fn random_operations() {
+        for seed in 1..=200 {
+            let mut rng = XorShift(seed);
+            // sizes from empty to very skewed
+            let mut models = Vec::new();
+            for _ in 0..2 {
+                let size = [0, 1, 5, 50, 400][rng.below(5)];
+                let range = 1 + rng.below(1000);
+                let model: BTreeMap<usize, usize> = (0..size)
+                    .map(|_| (rng.below(range), rng.below(1000)))
+                    .collect();
+                models.push(model);
+            }
+            let (a, b) = (&models[0], &models[1]);
+            let tree_a = || -> RedBlackTree<usize, usize> { a.clone().into_iter().collect() };
+            let tree_b = || -> RedBlackTree<usize, usize> { b.clone().into_iter().collect() };
+
+            let mut expected = b.clone();
+            for (&key, &value) in a {
+                *expected.entry(key).or_insert(0) += value;
+            }
+            let tree = tree_a().union_with(tree_b(), |_, value, other| *value += other);
+            assert_eq!(tree.check_invariants(), Ok(()));
+            assert!(tree.into_iter().eq(expected));
+
+            let expected: BTreeMap<_, _> = a.iter()
+                .filter_map(|(&key, &value)| Some((key, value * 1000 + b.get(&key)?)))
+                .collect();
+            let tree = tree_a().intersection_with(tree_b(), |_, value, other| {
+                *value = *value * 1000 + other;
+            });
+            assert_eq!(tree.check_invariants(), Ok(()));
+            assert!(tree.into_iter().eq(expected));
+
+            let expected: Vec<_> = a.iter()
+                .filter(|(key, _)| !b.contains_key(key))
+                .map(|(&key, &value)| (key, value))
+                .collect();
+            let tree = tree_a().difference(tree_b());
+            assert_eq!(tree.check_invariants(), Ok(()));
+            assert!(tree.into_iter().eq(expected));
+
+            let mut expected: Vec<_> = a.iter()
+                .chain(b)
+                .filter(|(key, _)| a.contains_key(key) != b.contains_key(key))
+                .map(|(&key, &value)| (key, value))
+                .collect();
+            expected.sort_unstable();
+            let tree = tree_a().symmetric_difference(tree_b());
+            assert_eq!(tree.check_invariants(), Ok(()));
+            assert!(tree.into_iter().eq(expected));
+        }
+    }
+}